@@ -1,7 +1,196 @@
-use criterion::{criterion_group, criterion_main, Criterion, SamplingMode};
+use criterion::{
+    criterion_group, criterion_main, BenchmarkId, Criterion, SamplingMode, Throughput,
+};
+use dupsrm::finder::{canonicalize_files, hash_files, prefilter_files};
 use dupsrm::hasher::*;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{BufReader, Read};
 use std::path::Path;
 
+/// Hash a file with sha256 using a read buffer of the given size
+fn sha256sum_with_buffer<const N: usize>(path: &Path) -> Vec<u8> {
+    let mut file = File::open(path).unwrap();
+    let mut hasher = Sha256::new();
+    let mut buffer = [0; N];
+    loop {
+        let count = file.read(&mut buffer).unwrap();
+        if count == 0 {
+            break;
+        }
+        hasher.update(&buffer[..count]);
+    }
+    hasher.finalize().to_vec()
+}
+
+pub fn benchmark_read_buffer_size(c: &mut Criterion) {
+    // test/test_large only measures the per-call overhead, so also hash a generated 16 MiB file
+    let generated = std::env::temp_dir().join("dupsrm_bench_16MiB");
+    fs::write(&generated, vec![0x5a; 16 << 20]).unwrap();
+    let mut group = c.benchmark_group("read-buffer-size");
+    group.sampling_mode(SamplingMode::Flat);
+    for path in [Path::new("test/test_large"), generated.as_path()] {
+        group.throughput(Throughput::Bytes(fs::metadata(path).unwrap().len()));
+        let name = path.file_name().unwrap().to_string_lossy();
+        group.bench_function(BenchmarkId::new(name.as_ref(), 4098), |b| {
+            b.iter(|| sha256sum_with_buffer::<4098>(path))
+        });
+        group.bench_function(BenchmarkId::new(name.as_ref(), READ_BUFFER_SIZE), |b| {
+            b.iter(|| sha256sum_with_buffer::<READ_BUFFER_SIZE>(path))
+        });
+    }
+    group.finish();
+    fs::remove_file(&generated).unwrap();
+}
+
+pub fn benchmark_mmap(c: &mut Criterion) {
+    // test/test_large only measures the per-call overhead, so also hash a generated 64 MiB file
+    let generated = std::env::temp_dir().join("dupsrm_bench_64MiB");
+    fs::write(&generated, vec![0x5a; 64 << 20]).unwrap();
+    let mut group = c.benchmark_group("mmap");
+    group.sampling_mode(SamplingMode::Flat);
+    for path in [Path::new("test/test_large"), generated.as_path()] {
+        group.throughput(Throughput::Bytes(fs::metadata(path).unwrap().len()));
+        let name = path.file_name().unwrap().to_string_lossy();
+        group.bench_function(BenchmarkId::new(name.as_ref(), "read"), |b| {
+            b.iter(|| hash_file(path, &HashAlgorithm::SHA2_256))
+        });
+        group.bench_function(BenchmarkId::new(name.as_ref(), "mmap"), |b| {
+            b.iter(|| hash_file_mapped(path, &HashAlgorithm::SHA2_256, 0))
+        });
+    }
+    group.finish();
+    fs::remove_file(&generated).unwrap();
+}
+
+pub fn benchmark_prefilter(c: &mut Criterion) {
+    // Large files of the same size with distinct leading bytes, only one of them duplicated
+    let dir = std::env::temp_dir().join("dupsrm_bench_prefilter");
+    let (root_dir, reference_dir) = (dir.join("root"), dir.join("reference"));
+    fs::create_dir_all(&root_dir).unwrap();
+    fs::create_dir_all(&reference_dir).unwrap();
+    let mut content = vec![0x5a; 4 << 20];
+    for i in 0..16u8 {
+        content[0] = i;
+        fs::write(root_dir.join(format!("file_{}", i)), &content).unwrap();
+        content[0] = i + 16 * (i > 0) as u8;
+        fs::write(reference_dir.join(format!("file_{}", i)), &content).unwrap();
+    }
+    let list = |dir: &Path| -> Vec<_> {
+        fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect()
+    };
+    let (root_files, reference_files) = (list(&root_dir), list(&reference_dir));
+    let (root_candidates, reference_candidates) =
+        prefilter_files(root_files.clone(), reference_files.clone());
+    println!(
+        "prefilter: {} of {} files hashed whole",
+        root_candidates.len() + reference_candidates.len(),
+        root_files.len() + reference_files.len()
+    );
+
+    let algorithm = HashAlgorithm::SHA2_256;
+    let hash_sum = |path: &Path| hash_file(path, &algorithm);
+    let mut group = c.benchmark_group("prefilter");
+    group.sampling_mode(SamplingMode::Flat);
+    group.bench_function("full", |b| {
+        b.iter(|| {
+            hash_files(root_files.clone(), hash_sum, &algorithm, false).len()
+                + hash_files(reference_files.clone(), hash_sum, &algorithm, false).len()
+        })
+    });
+    group.bench_function("two-phase", |b| {
+        b.iter(|| {
+            let (root_files, reference_files) =
+                prefilter_files(root_files.clone(), reference_files.clone());
+            hash_files(root_files, hash_sum, &algorithm, false).len()
+                + hash_files(reference_files, hash_sum, &algorithm, false).len()
+        })
+    });
+    group.finish();
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+pub fn benchmark_canonicalize(c: &mut Criterion) {
+    // Many small files, so the per-file path resolution weighs against the hashing
+    let dir = std::env::temp_dir()
+        .join("dupsrm_bench_canonicalize")
+        .join("a")
+        .join("b")
+        .join("c");
+    fs::create_dir_all(&dir).unwrap();
+    for i in 0..4096 {
+        fs::write(dir.join(format!("file_{}", i)), i.to_string()).unwrap();
+    }
+    let files: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+
+    let algorithm = HashAlgorithm::XXH3;
+    let hash_sum = |path: &Path| hash_file(path, &algorithm);
+    let mut group = c.benchmark_group("canonicalize");
+    group.throughput(Throughput::Elements(files.len() as u64));
+    group.bench_function("walked", |b| {
+        b.iter(|| hash_files(files.clone(), hash_sum, &algorithm, false).len())
+    });
+    group.bench_function("canonicalized", |b| {
+        b.iter(|| {
+            hash_files(
+                canonicalize_files(files.clone()),
+                hash_sum,
+                &algorithm,
+                false,
+            )
+            .len()
+        })
+    });
+    group.finish();
+    fs::remove_dir_all(std::env::temp_dir().join("dupsrm_bench_canonicalize")).unwrap();
+}
+
+/// Hash a file with sha256 like before the read buffer was reused, allocating one per file
+fn sha256sum_allocating(path: &Path) -> std::io::Result<Vec<u8>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; READ_BUFFER_SIZE];
+    loop {
+        let count = reader.read(&mut buffer)?;
+        if count == 0 {
+            break;
+        }
+        hasher.update(&buffer[..count]);
+    }
+    Ok(hasher.finalize().to_vec())
+}
+
+pub fn benchmark_small_files(c: &mut Criterion) {
+    // Many small files hashed in parallel, so the per-file allocations weigh against the hashing
+    let dir = std::env::temp_dir().join("dupsrm_bench_small_files");
+    fs::create_dir_all(&dir).unwrap();
+    for i in 0..4096 {
+        fs::write(dir.join(format!("file_{}", i)), i.to_string()).unwrap();
+    }
+    let files: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+
+    let algorithm = HashAlgorithm::SHA2_256;
+    let mut group = c.benchmark_group("small-files");
+    group.throughput(Throughput::Elements(files.len() as u64));
+    group.bench_function("allocated", |b| {
+        b.iter(|| hash_files(files.clone(), sha256sum_allocating, &algorithm, false).len())
+    });
+    group.bench_function("reused", |b| {
+        b.iter(|| hash_files(files.clone(), sha256sum, &algorithm, false).len())
+    });
+    group.finish();
+    fs::remove_dir_all(&dir).unwrap();
+}
+
 pub fn benchmark_sha256sum(c: &mut Criterion) {
     let path = Path::new("test/test_large");
     let mut group = c.benchmark_group("flat-sampling-hash-sum");
@@ -10,6 +199,22 @@ pub fn benchmark_sha256sum(c: &mut Criterion) {
     group.finish();
 }
 
+pub fn benchmark_sha384sum(c: &mut Criterion) {
+    let path = Path::new("test/test_large");
+    let mut group = c.benchmark_group("flat-sampling-hash-sum");
+    group.sampling_mode(SamplingMode::Flat);
+    group.bench_function("sha384sum", |b| b.iter(|| sha384sum(path)));
+    group.finish();
+}
+
+pub fn benchmark_sha512sum(c: &mut Criterion) {
+    let path = Path::new("test/test_large");
+    let mut group = c.benchmark_group("flat-sampling-hash-sum");
+    group.sampling_mode(SamplingMode::Flat);
+    group.bench_function("sha512sum", |b| b.iter(|| sha512sum(path)));
+    group.finish();
+}
+
 pub fn benchmark_sha3_256sum(c: &mut Criterion) {
     let path = Path::new("test/test_large");
     let mut group = c.benchmark_group("flat-sampling-hash-sum");
@@ -58,14 +263,57 @@ pub fn benchmark_blake256_sum(c: &mut Criterion) {
     group.finish();
 }
 
+pub fn benchmark_blake2b512_sum(c: &mut Criterion) {
+    let path = Path::new("test/test_large");
+    let mut group = c.benchmark_group("flat-sampling-hash-sum");
+    group.sampling_mode(SamplingMode::Flat);
+    group.bench_function("blake2b512_sum", |b| b.iter(|| blake2b512_sum(path)));
+    group.finish();
+}
+
+pub fn benchmark_blake2s256_sum(c: &mut Criterion) {
+    let path = Path::new("test/test_large");
+    let mut group = c.benchmark_group("flat-sampling-hash-sum");
+    group.sampling_mode(SamplingMode::Flat);
+    group.bench_function("blake2s256_sum", |b| b.iter(|| blake2s256_sum(path)));
+    group.finish();
+}
+
+pub fn benchmark_xxh3_sum(c: &mut Criterion) {
+    let path = Path::new("test/test_large");
+    let mut group = c.benchmark_group("flat-sampling-hash-sum");
+    group.sampling_mode(SamplingMode::Flat);
+    group.bench_function("xxh3_sum", |b| b.iter(|| xxh3_sum(path)));
+    group.finish();
+}
+
+pub fn benchmark_crc32_sum(c: &mut Criterion) {
+    let path = Path::new("test/test_large");
+    let mut group = c.benchmark_group("flat-sampling-hash-sum");
+    group.sampling_mode(SamplingMode::Flat);
+    group.bench_function("crc32_sum", |b| b.iter(|| crc32_sum(path)));
+    group.finish();
+}
+
 criterion_group!(
     benches,
     benchmark_sha256sum,
+    benchmark_sha384sum,
+    benchmark_sha512sum,
     benchmark_sha3_256sum,
     benchmark_sha1sum,
     benchmark_md5sum,
     benchmark_whirlpool_sum,
     benchmark_ripemd160_sum,
     benchmark_blake256_sum,
+    benchmark_blake2b512_sum,
+    benchmark_blake2s256_sum,
+    benchmark_xxh3_sum,
+    benchmark_crc32_sum,
+    benchmark_read_buffer_size,
+    benchmark_mmap,
+    benchmark_prefilter,
+    benchmark_canonicalize,
+    benchmark_small_files,
 );
 criterion_main!(benches);
@@ -0,0 +1,36 @@
+use criterion::{criterion_group, criterion_main, Criterion, SamplingMode};
+use dupsrm::finder::{walk_files, WalkOptions};
+use std::fs;
+use std::path::Path;
+
+/// Create a directory tree of the given depth with `fanout` subdirectories and files per level
+fn create_tree(dir: &Path, depth: usize, fanout: usize) {
+    fs::create_dir_all(dir).unwrap();
+    for i in 0..fanout {
+        fs::write(dir.join(format!("file_{}.txt", i)), i.to_string()).unwrap();
+        if depth > 0 {
+            create_tree(&dir.join(format!("dir_{}", i)), depth - 1, fanout);
+        }
+    }
+}
+
+pub fn benchmark_walk(c: &mut Criterion) {
+    // Deep tree of 4^6 directories
+    let root = std::env::temp_dir().join("dupsrm_bench_walk");
+    create_tree(&root, 6, 4);
+    let mut group = c.benchmark_group("walk");
+    group.sampling_mode(SamplingMode::Flat);
+    for parallel in [false, true] {
+        let options = WalkOptions {
+            parallel,
+            ..Default::default()
+        };
+        let name = if parallel { "jwalk" } else { "walkdir" };
+        group.bench_function(name, |b| b.iter(|| walk_files(&root, &[], &options)));
+    }
+    group.finish();
+    fs::remove_dir_all(&root).unwrap();
+}
+
+criterion_group!(benches, benchmark_walk);
+criterion_main!(benches);
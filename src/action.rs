@@ -0,0 +1,242 @@
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+#[cfg(target_os = "linux")]
+use std::ffi::{CString, OsStr};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, Write};
+#[cfg(target_os = "linux")]
+use std::os::unix::{ffi::OsStrExt, io::AsRawFd};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use walkdir::WalkDir;
+
+/// Enumerates the actions applied to duplicated reference files
+#[derive(Clone)]
+pub enum Action {
+    Remove,   //< Remove the file permanently
+    Trash,    //< Move the file to the trash of the operating system
+    Hardlink, //< Replace the file with a hard link to the matching root file
+    Symlink,  //< Replace the file with a symbolic link to the matching root file
+    Backup {
+        dir: PathBuf,          //< Backup directory the file is moved into
+        base: Option<PathBuf>, //< Directory the recreated file path is relative to
+    },
+}
+
+impl Action {
+    /// Apply the action to a duplicated reference file matching the root file `target`
+    ///
+    /// Returns the new path of the file if it was moved to a known location.
+    pub fn apply(&self, path: &Path, target: &Path) -> Result<Option<PathBuf>, io::Error> {
+        match self {
+            Action::Remove => fs::remove_file(path).map(|_| None),
+            Action::Trash => trash::delete(path).map(|_| None).map_err(io::Error::other),
+            Action::Hardlink => replace_with_link(path, |link| fs::hard_link(target, link))
+                .map(|_| None)
+                .map_err(|err| match err.kind() {
+                    io::ErrorKind::CrossesDevices => io::Error::new(
+                        err.kind(),
+                        format!(
+                            "cannot hard link to {} on a different filesystem",
+                            target.to_str().unwrap()
+                        ),
+                    ),
+                    _ => err,
+                }),
+            Action::Symlink => replace_with_link(path, |link| symlink(target, link)).map(|_| None),
+            Action::Backup { dir, base } => backup(path, dir, base.as_deref()).map(Some),
+        }
+    }
+
+    /// Verb describing the ongoing action in log messages
+    pub fn present_participle(&self) -> &'static str {
+        match self {
+            Action::Remove => "Removing",
+            Action::Trash => "Trashing",
+            Action::Hardlink => "Hardlinking",
+            Action::Symlink => "Symlinking",
+            Action::Backup { .. } => "Backing up",
+        }
+    }
+
+    /// Verb describing the completed action in log messages
+    pub fn past_tense(&self) -> &'static str {
+        match self {
+            Action::Remove => "Removed",
+            Action::Trash => "Trashed",
+            Action::Hardlink => "Hardlinked",
+            Action::Symlink => "Symlinked",
+            Action::Backup { .. } => "Backed up",
+        }
+    }
+}
+
+/// Create a symbolic link at `link` pointing to `target`
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> Result<(), io::Error> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+/// Create a symbolic link at `link` pointing to `target`
+#[cfg(windows)]
+fn symlink(target: &Path, link: &Path) -> Result<(), io::Error> {
+    std::os::windows::fs::symlink_file(target, link)
+}
+
+/// Create a symbolic link at `link` pointing to `target`
+#[cfg(not(any(unix, windows)))]
+fn symlink(_target: &Path, _link: &Path) -> Result<(), io::Error> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+/// Replace a file with a link created by `link`
+///
+/// The link is created next to the file and renamed over it, so the file is left in place if
+/// the link can't be created.
+fn replace_with_link<F>(path: &Path, link: F) -> Result<(), io::Error>
+where
+    F: Fn(&Path) -> Result<(), io::Error>,
+{
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".dupsrm-link");
+    let link_path: PathBuf = path.with_file_name(name);
+    link(&link_path)?;
+    fs::rename(&link_path, path).inspect_err(|_| {
+        let _ = fs::remove_file(&link_path);
+    })
+}
+
+/// Move a file into a backup directory and return its new path
+///
+/// The path of the file relative to `base` is recreated in the backup directory, files outside
+/// of `base` keep their full path. If the destination exists, a counter is appended to the file
+/// name. Files are copied and removed if the backup directory is on a different filesystem.
+fn backup(path: &Path, dir: &Path, base: Option<&Path>) -> Result<PathBuf, io::Error> {
+    let relative: PathBuf = match base.and_then(|base| path.strip_prefix(base).ok()) {
+        Some(relative) => relative.to_path_buf(),
+        None => path
+            .components()
+            .filter(|component| matches!(component, Component::Normal(_)))
+            .collect(),
+    };
+    let destination = dir.join(relative);
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    // Reserve a free destination, so concurrent backups never overwrite each other
+    let mut candidate = destination.clone();
+    for counter in 1.. {
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&candidate)
+        {
+            Ok(_) => break,
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                let mut name = destination.file_name().unwrap_or_default().to_os_string();
+                name.push(format!(".{}", counter));
+                candidate = destination.with_file_name(name);
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    let moved = match fs::rename(path, &candidate) {
+        Err(err) if err.kind() == io::ErrorKind::CrossesDevices => {
+            fs::copy(path, &candidate).and_then(|_| fs::remove_file(path))
+        }
+        result => result,
+    };
+    moved
+        .inspect_err(|_| {
+            let _ = fs::remove_file(&candidate);
+        })
+        .map(|_| candidate)
+}
+
+/// Remove a directory tree left without files, e.g. after its files were processed
+///
+/// Fails once a directory can't be removed, e.g. as a file is left in it, keeping its parents.
+pub fn remove_empty_dirs(dir: &Path) -> Result<(), io::Error> {
+    for entry in WalkDir::new(dir).contents_first(true) {
+        let entry = entry?;
+        if entry.file_type().is_dir() {
+            fs::remove_dir(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+/// Ask a question on `writer` and read the answer from `reader`
+///
+/// Only `y` or `yes` in any case confirm, anything else including no answer declines.
+pub fn confirm<R: BufRead, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    question: &str,
+) -> io::Result<bool> {
+    write!(writer, "{} [y/N] ", question)?;
+    writer.flush()?;
+    let mut answer = String::new();
+    reader.read_line(&mut answer)?;
+    let answer = answer.trim().to_lowercase();
+    Ok(answer == "y" || answer == "yes")
+}
+
+/// Remove files grouped by their parent directory
+///
+/// On Linux each directory is opened once and its files are unlinked relative to the directory
+/// file descriptor, avoiding the repeated resolution of the full paths. Other platforms remove
+/// each file by its path. The results are returned in the order of the paths. Unless
+/// `keep_going` is set, no file is unlinked after the first failure, its result is `None`.
+pub fn remove_batched(paths: &[&Path], keep_going: bool) -> Vec<Option<Result<(), io::Error>>> {
+    let mut groups: BTreeMap<&Path, Vec<usize>> = BTreeMap::new();
+    for (index, path) in paths.iter().enumerate() {
+        groups
+            .entry(path.parent().unwrap_or(Path::new("")))
+            .or_default()
+            .push(index);
+    }
+
+    let failed = AtomicBool::new(false);
+    let mut results: Vec<(usize, Option<Result<(), io::Error>>)> = groups
+        .into_par_iter()
+        .flat_map_iter(|(dir, indices)| {
+            let dir = File::open(dir);
+            let failed = &failed;
+            indices.into_iter().map(move |index| {
+                if !keep_going && failed.load(Ordering::Relaxed) {
+                    return (index, None);
+                }
+                let result = match &dir {
+                    Ok(dir) => unlink_in(dir, paths[index]),
+                    Err(err) => Err(io::Error::new(err.kind(), err.to_string())),
+                };
+                if result.is_err() {
+                    failed.store(true, Ordering::Relaxed);
+                }
+                (index, Some(result))
+            })
+        })
+        .collect();
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Unlink a file relative to the file descriptor of its parent directory
+#[cfg(target_os = "linux")]
+fn unlink_in(dir: &File, path: &Path) -> Result<(), io::Error> {
+    let name = CString::new(path.file_name().unwrap_or(OsStr::new("")).as_bytes())?;
+    // SAFETY: the directory file descriptor is open and the name is a valid C string
+    match unsafe { libc::unlinkat(dir.as_raw_fd(), name.as_ptr(), 0) } {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+/// Remove a file by its path
+#[cfg(not(target_os = "linux"))]
+fn unlink_in(_dir: &File, path: &Path) -> Result<(), io::Error> {
+    fs::remove_file(path)
+}
@@ -0,0 +1,74 @@
+use super::hasher::{from_hex, to_hex};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// Cached hash value of a file, valid as long as its size and modification time are unchanged
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    size: u64,
+    modified: SystemTime,
+    algorithm: String,
+    hash: String,
+}
+
+/// On-disk cache of file hashes keyed by path
+pub struct HashCache {
+    entries: Mutex<HashMap<PathBuf, CacheEntry>>,
+}
+
+impl HashCache {
+    /// Load the cache from a JSON file, a missing file yields an empty cache
+    pub fn load(path: &Path) -> io::Result<HashCache> {
+        let entries = match File::open(path) {
+            Ok(file) => serde_json::from_reader(BufReader::new(file))?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(err) => return Err(err),
+        };
+        Ok(HashCache {
+            entries: Mutex::new(entries),
+        })
+    }
+
+    /// Save the cache as a JSON file
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(&mut writer, &*self.entries.lock().unwrap())?;
+        writer.flush()
+    }
+
+    /// Return the cached hash of a file or hash it and update the cache
+    ///
+    /// The cached hash is reused only if it was computed with the same algorithm and the size
+    /// and modification time of the file are unchanged.
+    pub fn hash<F>(&self, path: &Path, algorithm: &str, hash_sum: F) -> io::Result<Vec<u8>>
+    where
+        F: Fn(&Path) -> io::Result<Vec<u8>>,
+    {
+        let metadata = fs::metadata(path)?;
+        let (size, modified) = (metadata.len(), metadata.modified()?);
+        if let Some(entry) = self.entries.lock().unwrap().get(path) {
+            if entry.size == size && entry.modified == modified && entry.algorithm == algorithm {
+                if let Ok(hash) = from_hex(&entry.hash) {
+                    return Ok(hash);
+                }
+            }
+        }
+
+        let hash = hash_sum(path)?;
+        self.entries.lock().unwrap().insert(
+            path.to_path_buf(),
+            CacheEntry {
+                size,
+                modified,
+                algorithm: algorithm.to_owned(),
+                hash: to_hex(&hash),
+            },
+        );
+        Ok(hash)
+    }
+}
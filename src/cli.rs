@@ -1,49 +1,529 @@
+use super::finder::{KeepPolicy, RootPreference, TreePriority};
 use super::hasher::HashAlgorithm;
+use super::index::parse_index_key;
+use super::path::resolve;
+use byte_unit::Byte;
 use clap::builder::PossibleValue;
 pub use clap::{ArgAction, Parser, ValueEnum};
-use std::path::PathBuf;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
 
 impl ValueEnum for HashAlgorithm {
+    fn value_variants<'a>() -> &'a [Self] {
+        &Self::VARIANTS
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(PossibleValue::new(self.name()))
+    }
+}
+
+/// Enumerates the choices of the hash algorithm on the command line
+#[derive(Clone)]
+pub enum AlgorithmChoice {
+    Auto,                 //< XXH3 if duplicates are verified byte by byte, else SHA2-256
+    Given(HashAlgorithm), //< Algorithm given by name
+}
+
+impl AlgorithmChoice {
+    /// Auto followed by every hash algorithm, derived from the algorithms' own variants
+    fn variants() -> &'static [AlgorithmChoice] {
+        static VARIANTS: OnceLock<Vec<AlgorithmChoice>> = OnceLock::new();
+        VARIANTS.get_or_init(|| {
+            std::iter::once(AlgorithmChoice::Auto)
+                .chain(
+                    HashAlgorithm::value_variants()
+                        .iter()
+                        .cloned()
+                        .map(AlgorithmChoice::Given),
+                )
+                .collect()
+        })
+    }
+}
+
+impl ValueEnum for AlgorithmChoice {
+    fn value_variants<'a>() -> &'a [Self] {
+        Self::variants()
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        match self {
+            Self::Auto => Some(PossibleValue::new("auto")),
+            Self::Given(algorithm) => algorithm.to_possible_value(),
+        }
+    }
+}
+
+impl ValueEnum for RootPreference {
     fn value_variants<'a>() -> &'a [Self] {
         &[
-            Self::SHA2_256,
-            Self::SHA3_256,
-            Self::SHA1,
-            Self::MD5,
-            Self::WHIRLPOOL,
-            Self::RIPEMD160,
-            Self::BLAKE256,
+            Self::ShortestPath,
+            Self::Oldest,
+            Self::Newest,
+            Self::MostLinked,
         ]
     }
 
     fn to_possible_value(&self) -> Option<PossibleValue> {
         Some(match self {
-            Self::SHA2_256 => PossibleValue::new("SHA2-256"),
-            Self::SHA3_256 => PossibleValue::new("SHA3-256"),
-            Self::SHA1 => PossibleValue::new("SHA1"),
-            Self::MD5 => PossibleValue::new("MD5"),
-            Self::WHIRLPOOL => PossibleValue::new("WHIRLPOOL"),
-            Self::RIPEMD160 => PossibleValue::new("RIPEMD-160"),
-            Self::BLAKE256 => PossibleValue::new("BLAKE-256"),
+            Self::ShortestPath => PossibleValue::new("shortest-path"),
+            Self::Oldest => PossibleValue::new("oldest"),
+            Self::Newest => PossibleValue::new("newest"),
+            Self::MostLinked => PossibleValue::new("most-linked"),
+        })
+    }
+}
+
+impl ValueEnum for TreePriority {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Self::Reference, Self::Root]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(match self {
+            Self::Reference => PossibleValue::new("reference"),
+            Self::Root => PossibleValue::new("root"),
+        })
+    }
+}
+
+impl ValueEnum for KeepPolicy {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Self::First, Self::Oldest, Self::Newest]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(match self {
+            Self::First => PossibleValue::new("first"),
+            Self::Oldest => PossibleValue::new("oldest"),
+            Self::Newest => PossibleValue::new("newest"),
+        })
+    }
+}
+
+/// Enumerates the formats of the error aborting a run
+#[derive(Clone, PartialEq)]
+pub enum ErrorFormat {
+    Text, //< Human-readable message
+    Json, //< JSON object with the message and the kind of error
+}
+
+impl ValueEnum for ErrorFormat {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Self::Text, Self::Json]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(match self {
+            Self::Text => PossibleValue::new("text"),
+            Self::Json => PossibleValue::new("json"),
         })
     }
 }
 
+/// Enumerates the output formats of the found duplicates
+#[derive(Clone, PartialEq)]
+pub enum OutputFormat {
+    Text, //< Log messages
+    Json, //< JSON array written to stdout
+    Csv,  //< CSV table with a header row written to stdout
+}
+
+impl ValueEnum for OutputFormat {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Self::Text, Self::Json, Self::Csv]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(match self {
+            Self::Text => PossibleValue::new("text"),
+            Self::Json => PossibleValue::new("json"),
+            Self::Csv => PossibleValue::new("csv"),
+        })
+    }
+}
+
+/// Parse a human-friendly size like `10MB` or `500K` into bytes
+fn parse_size(size: &str) -> Result<u64, String> {
+    Byte::parse_str(size, true)
+        .map(|byte| byte.as_u64())
+        .map_err(|err| err.to_string())
+}
+
+/// Parse a human-friendly size of at least one byte, e.g. a length of leading bytes
+fn parse_length(length: &str) -> Result<u64, String> {
+    match parse_size(length)? {
+        0 => Err("must be at least 1 byte".to_string()),
+        length => Ok(length),
+    }
+}
+
+/// Parse a human-friendly duration like `7d` or `24h`
+fn parse_duration(duration: &str) -> Result<Duration, String> {
+    humantime::parse_duration(duration).map_err(|err| err.to_string())
+}
+
+/// Parse a fraction between 0.0 and 1.0
+fn parse_fraction(fraction: &str) -> Result<f64, String> {
+    match fraction.parse::<f64>() {
+        Ok(fraction) if (0.0..=1.0).contains(&fraction) => Ok(fraction),
+        Ok(_) => Err("must be between 0.0 and 1.0".to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// Parse a walk depth of at least 1, the direct children of a directory
+fn parse_depth(depth: &str) -> Result<usize, String> {
+    match depth.parse::<usize>() {
+        Ok(0) => Err("must be at least 1".to_string()),
+        Ok(depth) => Ok(depth),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
 /// Remove duplicated files in the reference directory that are found in the root directory tree.
 #[derive(Parser)]
 #[clap(author = "Manuel Amersdorfer", version)]
 pub struct Cli {
-    /// Reference directory path
-    pub reference_dir: PathBuf,
-    /// Root directory path
-    pub root_dir: PathBuf,
-    /// Perform a dry-run without removing any file
+    /// Reference directory path, or `-` to read a list of reference files from stdin
+    #[clap(required_unless_present_any = ["merge_reports", "reference_from"])]
+    pub reference_dir: Option<PathBuf>,
+    /// Root directory paths, their files are combined into the set of known files
+    #[clap(num_args = 1.., required_unless_present_any = ["verify_manifest", "merge_reports", "within", "root_checksums", "root_from"])]
+    pub root_dirs: Vec<PathBuf>,
+    /// Resolve relative directory and file paths against this directory instead of the working directory
+    #[clap(long)]
+    pub base_dir: Option<PathBuf>,
+    /// Abort if the reference path or a root path is not a directory instead of warning
+    #[clap(long, action(ArgAction::SetTrue))]
+    pub require_dirs: bool,
+    /// Allow the reference directory and a root directory to be nested inside each other
+    #[clap(long, action(ArgAction::SetTrue))]
+    pub allow_overlap: bool,
+    /// Perform a dry-run without removing any file, writing the found duplicates to stdout
     #[clap(long, short = 'n', action(ArgAction::SetTrue))]
     pub dry_run: bool,
+    /// Separate the found duplicates written to stdout by NUL characters instead of newlines
+    #[clap(
+        long,
+        alias = "0",
+        requires = "dry_run",
+        conflicts_with = "format",
+        action(ArgAction::SetTrue)
+    )]
+    pub print0: bool,
+    /// Exit with code 1 if duplicates were found, 2 on errors and 0 otherwise, logging nothing but problems
+    #[clap(
+        long,
+        requires = "dry_run",
+        conflicts_with_all = ["format", "print0"],
+        action(ArgAction::SetTrue)
+    )]
+    pub exit_code: bool,
+    /// Print the totals of the run as a single JSON object to stdout at the end
+    #[clap(
+        long,
+        conflicts_with_all = ["format", "print0", "exit_code"],
+        action(ArgAction::SetTrue)
+    )]
+    pub json_summary: bool,
+    /// Only write the number of found duplicates to stdout, implying --dry-run
+    #[clap(
+        long,
+        conflicts_with_all = ["format", "print0", "exit_code", "json_summary", "confirm_once"],
+        action(ArgAction::SetTrue)
+    )]
+    pub count_only: bool,
+    /// Allow removing files when running as root
+    #[clap(long, action(ArgAction::SetTrue))]
+    pub allow_root: bool,
+    /// Abort without touching any file if more than this number of duplicates are found
+    #[clap(long)]
+    pub max_delete: Option<usize>,
+    /// Abort without touching any file if more than this fraction of the reference files, e.g. 0.5, are duplicates
+    #[clap(long, value_parser = parse_fraction)]
+    pub max_delete_fraction: Option<f64>,
+    /// List the duplicates and ask once on stdin before touching any file
+    #[clap(long, conflicts_with = "dry_run", action(ArgAction::SetTrue))]
+    pub confirm_once: bool,
+    /// Hash each duplicate again right before processing it and skip it if its content changed
+    #[clap(long, conflicts_with_all = ["trust_attr", "batch_unlink"], action(ArgAction::SetTrue))]
+    pub recheck_before_delete: bool,
+    /// Keep processing duplicates after a failure, exiting with code 2 instead of 1
+    #[clap(long, action(ArgAction::SetTrue))]
+    pub keep_going: bool,
+    /// Move duplicates to the trash of the operating system instead of removing them
+    #[clap(long, action(ArgAction::SetTrue))]
+    pub trash: bool,
+    /// Remove duplicates grouped by directory, relative to a directory file descriptor on Linux
+    #[clap(long, conflicts_with = "trash", action(ArgAction::SetTrue))]
+    pub batch_unlink: bool,
+    /// Replace duplicates with hard links to the matching root files instead of removing them
+    #[clap(long, conflicts_with_all = ["trash", "batch_unlink"], action(ArgAction::SetTrue))]
+    pub hardlink: bool,
+    /// Replace duplicates with symbolic links to the matching root files instead of removing them
+    #[clap(long, conflicts_with_all = ["trash", "batch_unlink", "hardlink"], action(ArgAction::SetTrue))]
+    pub symlink: bool,
+    /// Move duplicates into this directory, recreating their paths relative to the reference directory
+    #[clap(long, conflicts_with_all = ["trash", "batch_unlink", "hardlink", "symlink"])]
+    pub backup_dir: Option<PathBuf>,
+    /// Follow symbolic links while walking the directory trees, skipping link cycles
+    #[clap(long, action(ArgAction::SetTrue))]
+    pub follow_symlinks: bool,
+    /// Follow symbolic links while walking the reference directory tree only
+    #[clap(long, conflicts_with = "follow_symlinks", action(ArgAction::SetTrue))]
+    pub follow_reference_symlinks: bool,
+    /// Follow symbolic links while walking the root directory trees only
+    #[clap(long, conflicts_with = "follow_symlinks", action(ArgAction::SetTrue))]
+    pub follow_root_symlinks: bool,
+    /// Read directories in parallel, e.g. on network filesystems with a high latency
+    #[clap(long, action(ArgAction::SetTrue))]
+    pub parallel_walk: bool,
+    /// Skip files ignored by `.gitignore`, `.ignore` and global git excludes while walking
+    #[clap(long, conflicts_with = "parallel_walk", action(ArgAction::SetTrue))]
+    pub respect_gitignore: bool,
+    /// Number of threads walking, hashing and comparing files, 0 uses all cores
+    #[clap(long, default_value_t = 0)]
+    pub threads: usize,
+    /// Abort on the first directory entry failing to be read instead of skipping it
+    #[clap(long, action(ArgAction::SetTrue))]
+    pub strict: bool,
+    /// Separate the reference files read from stdin by NUL characters instead of newlines
+    #[clap(long, action(ArgAction::SetTrue))]
+    pub null: bool,
+    /// Read the reference files from this list of NUL-separated paths instead of walking a directory
+    #[clap(long, conflicts_with = "reference_dir")]
+    pub reference_from: Option<PathBuf>,
+    /// Read the root files from this list of NUL-separated paths instead of walking directories
+    #[clap(long, conflicts_with_all = ["root_dirs", "root_checksums"])]
+    pub root_from: Option<PathBuf>,
     /// Regular expression filtering files in reference directories
+    ///
+    /// Matched against the path relative to the reference directory, e.g. '^photos/', or the
+    /// full path of files read from stdin.
     #[clap(long, short)]
     pub regex: Option<String>,
-    /// Hash algorithm
-    #[clap(long, short = 'a', default_value = "SHA2-256")]
+    /// Match --regex case-insensitively, like the inline flag '(?i)'
+    #[clap(long, action(ArgAction::SetTrue), requires = "regex")]
+    pub regex_ignore_case: bool,
+    /// Regular expression filtering files in root directories, independent of --regex
+    ///
+    /// If both are set, a reference file is only removed if it matches --regex and duplicates a
+    /// root file matching --root-regex.
+    #[clap(long)]
+    pub root_regex: Option<String>,
+    /// Also match empty files, which are skipped by default
+    #[clap(long, action(ArgAction::SetTrue))]
+    pub keep_empty: bool,
+    /// Name of directories pruned from both walks, e.g. 'node_modules', may be repeated
+    #[clap(long)]
+    pub exclude_dir: Vec<OsString>,
+    /// Skip reference directories on another filesystem than the reference directory, like `find -xdev`
+    #[clap(long, action(ArgAction::SetTrue))]
+    pub one_file_system: bool,
+    /// Skip hidden files and directories, whose name starts with a dot, in both walks
+    #[clap(long, action(ArgAction::SetTrue))]
+    pub ignore_hidden: bool,
+    /// Only walk the reference directory this many levels deep, 1 being its direct children
+    #[clap(long, visible_alias = "max-depth", value_parser = parse_depth)]
+    pub reference_max_depth: Option<usize>,
+    /// Only walk the root directories this many levels deep, 1 being their direct children
+    #[clap(long, value_parser = parse_depth)]
+    pub root_max_depth: Option<usize>,
+    /// Glob pattern selecting reference files, e.g. '*.jpg', may be repeated
+    #[clap(long)]
+    pub include: Vec<String>,
+    /// Glob pattern excluding reference files, e.g. '*.tmp', may be repeated
+    #[clap(long)]
+    pub exclude: Vec<String>,
+    /// Only consider files of at least this size, e.g. 10MB or 500K
+    #[clap(long, value_parser = parse_size)]
+    pub min_size: Option<u64>,
+    /// Only consider files of at most this size, e.g. 10MB or 500K
+    #[clap(long, value_parser = parse_size)]
+    pub max_size: Option<u64>,
+    /// Hash algorithm, `auto` picks the fast XXH3 unless --no-verify is given
+    #[clap(
+        long = "hash-algorithm",
+        short = 'a',
+        visible_alias = "algorithm",
+        value_name = "HASH_ALGORITHM",
+        default_value = "SHA2-256"
+    )]
+    pub algorithm_choice: AlgorithmChoice,
+    /// Hash algorithm picked by `resolve_hash_algorithm`
+    #[clap(skip = HashAlgorithm::SHA2_256)]
     pub hash_algorithm: HashAlgorithm,
+    /// Memory-map files of at least this size while hashing, e.g. 64MiB, instead of reading them in chunks
+    #[clap(long, value_parser = parse_size)]
+    pub mmap_threshold: Option<u64>,
+    /// Only hash the first bytes of files, e.g. 1MiB, taking files of the same size sharing them as duplicates
+    ///
+    /// Duplicates are only compared up to this length too, unless --verify-prefix or --no-verify
+    /// is given. Differences after it go unnoticed.
+    #[clap(
+        long,
+        value_parser = parse_length,
+        conflicts_with_all = ["mmap_threshold", "trust_attr", "cache", "root_checksums", "write_checksums"]
+    )]
+    pub hash_bytes: Option<u64>,
+    /// Retry reading a file failing to be hashed this number of times, doubling the delay each time
+    #[clap(long, default_value_t = 0)]
+    pub io_retries: u32,
+    /// Match files by the value of this trusted extended attribute instead of hashing their content
+    #[clap(long)]
+    pub trust_attr: Option<String>,
+    /// Cache file reusing the hashes of files with unchanged size and modification time
+    #[clap(long)]
+    pub cache: Option<PathBuf>,
+    /// Skip the byte-by-byte comparison of duplicates and trust hash equality
+    ///
+    /// Ignored for non-cryptographic hash algorithms like XXH3 and CRC32.
+    #[clap(long, action(ArgAction::SetTrue))]
+    pub no_verify: bool,
+    /// Only compare the first bytes of duplicates, e.g. 1MiB, instead of their whole content
+    #[clap(long, value_parser = parse_size, conflicts_with = "no_verify")]
+    pub verify_prefix: Option<u64>,
+    /// Only match files if a root file with the same content also has the same file name
+    #[clap(long, action(ArgAction::SetTrue))]
+    pub same_name_only: bool,
+    /// Policy choosing the matching root file if several root files have identical content
+    ///
+    /// The chosen file is the target of --hardlink and --symlink.
+    #[clap(long, visible_alias = "prefer", default_value = "shortest-path")]
+    pub prefer_root_by: RootPreference,
+    /// Keep one of the reference duplicates sharing their content, chosen by --prefer-root-by
+    #[clap(long, conflicts_with_all = ["priority", "within"], action(ArgAction::SetTrue))]
+    pub keep_one_reference: bool,
+    /// Match whole reference directories holding the same files as a root directory, removing them as a unit
+    ///
+    /// Directories are hashed by the relative paths and hashes of all files below them, empty
+    /// files included. The files of matching directories are processed, then the emptied
+    /// directories are removed. Directories holding anything else, e.g. a file skipped by a
+    /// filter or below a pruned directory, never match.
+    #[clap(
+        long,
+        conflicts_with_all = [
+            "within", "priority", "keep_one_reference", "same_name_only", "hardlink", "symlink",
+            "reference_from", "root_from", "root_checksums"
+        ],
+        action(ArgAction::SetTrue)
+    )]
+    pub dirs: bool,
+    /// Deduplicate across both trees, keeping one copy per content in the given tree if possible
+    #[clap(long)]
+    pub priority: Option<TreePriority>,
+    /// Deduplicate within the reference directory alone, keeping one copy per content
+    #[clap(long, conflicts_with_all = ["root_dirs", "priority", "same_name_only"], action(ArgAction::SetTrue))]
+    pub within: bool,
+    /// Policy choosing the copy kept per content with --within
+    #[clap(long, default_value = "first")]
+    pub keep: KeepPolicy,
+    /// Key of the SipHash bucketing of the hash index as hexadecimal number, random by default
+    #[clap(long, value_parser = parse_index_key)]
+    pub index_key: Option<u128>,
+    /// Only consider reference files modified after the modification time of this marker file
+    #[clap(long)]
+    pub since: Option<PathBuf>,
+    /// Only consider reference files last modified longer ago than this duration, e.g. 7d
+    #[clap(long, value_parser = parse_duration)]
+    pub older_than: Option<Duration>,
+    /// Only consider reference files last modified within this duration, e.g. 24h
+    #[clap(long, value_parser = parse_duration)]
+    pub newer_than: Option<Duration>,
+    /// Update the modification time of the --since marker file at the end of the run
+    #[clap(long, requires = "since", action(ArgAction::SetTrue))]
+    pub touch_marker: bool,
+    /// Output format of the found duplicates
+    #[clap(long, default_value = "text")]
+    pub format: OutputFormat,
+    /// Append a JSON line per processed duplicate to this file, e.g. to audit or restore removals
+    #[clap(long)]
+    pub log_removed: Option<PathBuf>,
+    /// Format of the error aborting a run, written to stderr
+    #[clap(long, default_value = "text")]
+    pub error_format: ErrorFormat,
+    /// Load the root hashes from a checksum file in the coreutils format instead of walking the root directories
+    ///
+    /// Relative paths are resolved against the first root directory, if any. The file must be
+    /// written with the selected hash algorithm.
+    #[clap(long, conflicts_with_all = ["within", "priority", "trust_attr"])]
+    pub root_checksums: Option<PathBuf>,
+    /// Write the hashes of the reference files to this file in the coreutils checksum format
+    #[clap(long, conflicts_with = "trust_attr")]
+    pub write_checksums: Option<PathBuf>,
+    /// Write a GraphViz DOT graph of files sharing a hash across both trees
+    #[clap(long)]
+    pub dot: Option<PathBuf>,
+    /// Minimum number of files sharing a hash to be included in the DOT graph
+    #[clap(long, default_value_t = 2, requires = "dot")]
+    pub min_group: usize,
+    /// Verify the files of the reference directory against a manifest in checksum format
+    #[clap(long)]
+    pub verify_manifest: Option<PathBuf>,
+    /// Rewrite the manifest from the current files after verifying it
+    #[clap(long, requires = "verify_manifest", action(ArgAction::SetTrue))]
+    pub repair_manifest: bool,
+    /// Hide the progress bar of the hashing phases
+    #[clap(long, action(ArgAction::SetTrue))]
+    pub no_progress: bool,
+    /// Print the wall-clock duration of each phase of the run
+    #[clap(long, action(ArgAction::SetTrue))]
+    pub timings: bool,
+    /// Print the hashing throughput of the run, to help picking an algorithm
+    #[clap(long, action(ArgAction::SetTrue))]
+    pub verbose: bool,
+    /// Merge JSON reports of previous runs into a summary without scanning any directory
+    #[clap(long, num_args = 1.., conflicts_with_all = ["reference_dir", "root_dirs"])]
+    pub merge_reports: Vec<PathBuf>,
+    /// Report an estimate of the peak memory used by the hash index and read buffers
+    #[clap(long, action(ArgAction::SetTrue))]
+    pub report_memory: bool,
+}
+
+impl Cli {
+    /// Pick the hash algorithm, if left to choose by the verification of duplicates
+    ///
+    /// Verified duplicates only need the hash to narrow down the candidates, which a fast
+    /// non-cryptographic hash does as well. Trusted hash equality needs a cryptographic one.
+    pub fn resolve_hash_algorithm(&mut self) {
+        self.hash_algorithm = match &self.algorithm_choice {
+            AlgorithmChoice::Auto if self.no_verify => HashAlgorithm::SHA2_256,
+            AlgorithmChoice::Auto => HashAlgorithm::XXH3,
+            AlgorithmChoice::Given(algorithm) => algorithm.clone(),
+        };
+    }
+
+    /// Resolve all relative paths against the base directory, if one is given
+    pub fn resolve_paths(&mut self) {
+        let base_dir = match &self.base_dir {
+            Some(base_dir) => base_dir.clone(),
+            None => return,
+        };
+        let resolve_option = |path: &mut Option<PathBuf>| {
+            if let Some(path) = path {
+                *path = resolve(path, &base_dir);
+            }
+        };
+        if self.reference_dir.as_deref() != Some(Path::new("-")) {
+            resolve_option(&mut self.reference_dir);
+        }
+        resolve_option(&mut self.reference_from);
+        resolve_option(&mut self.root_from);
+        resolve_option(&mut self.since);
+        resolve_option(&mut self.backup_dir);
+        resolve_option(&mut self.cache);
+        resolve_option(&mut self.dot);
+        resolve_option(&mut self.write_checksums);
+        resolve_option(&mut self.root_checksums);
+        resolve_option(&mut self.log_removed);
+        resolve_option(&mut self.verify_manifest);
+        self.root_dirs
+            .iter_mut()
+            .chain(self.merge_reports.iter_mut())
+            .for_each(|path| *path = resolve(path, &base_dir));
+    }
 }
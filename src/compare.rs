@@ -0,0 +1,33 @@
+use super::hasher::READ_BUFFER_SIZE;
+use std::cmp::min;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+
+/// Compare the content of two files byte by byte
+pub fn is_identical(path_a: &Path, path_b: &Path) -> Result<bool, io::Error> {
+    is_prefix_identical(path_a, path_b, u64::MAX)
+}
+
+/// Compare the sizes and the first `length` bytes of two files
+pub fn is_prefix_identical(path_a: &Path, path_b: &Path, length: u64) -> Result<bool, io::Error> {
+    if fs::metadata(path_a)?.len() != fs::metadata(path_b)?.len() {
+        return Ok(false);
+    }
+
+    let mut reader_a = BufReader::with_capacity(READ_BUFFER_SIZE, File::open(path_a)?.take(length));
+    let mut reader_b = BufReader::with_capacity(READ_BUFFER_SIZE, File::open(path_b)?.take(length));
+    loop {
+        let buffer_a = reader_a.fill_buf()?;
+        let buffer_b = reader_b.fill_buf()?;
+        if buffer_a.is_empty() || buffer_b.is_empty() {
+            return Ok(buffer_a.is_empty() && buffer_b.is_empty());
+        }
+        let count = min(buffer_a.len(), buffer_b.len());
+        if buffer_a[..count] != buffer_b[..count] {
+            return Ok(false);
+        }
+        reader_a.consume(count);
+        reader_b.consume(count);
+    }
+}
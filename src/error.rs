@@ -1,27 +1,85 @@
 use std::error::Error;
 use std::fmt;
+use std::io;
+use std::path::PathBuf;
 
+/// Enumerates the errors aborting a run
 #[derive(Debug)]
-pub struct ArgumentError {
-    details: String,
+pub enum DupsrmError {
+    SameDirectory,          //< Reference directory is identical to a root directory
+    InvalidRegex(String),   //< Regular expression failing to compile, with the reason
+    InvalidGlob(String),    //< Glob pattern failing to compile, with the reason
+    NotADirectory(PathBuf), //< Reference or root path required to be a directory is not one
+    Argument(String),       //< Other invalid argument or combination of arguments
+    Verification(String),   //< Files don't match the manifest
+    Removal(String),        //< Processing some duplicates failed
+    Io(io::Error),          //< Reading or writing a file or directory failed
 }
 
-impl ArgumentError {
-    pub fn new(msg: &str) -> Box<ArgumentError> {
-        Box::new(ArgumentError {
-            details: msg.to_string(),
-        })
+/// Former error of invalid arguments, replaced by the variants of `DupsrmError`
+#[deprecated(note = "use `DupsrmError` instead")]
+pub type ArgumentError = DupsrmError;
+
+impl DupsrmError {
+    /// Name of the variant, telling the kind of error to tools
+    pub fn kind(&self) -> &'static str {
+        match self {
+            DupsrmError::SameDirectory => "SameDirectory",
+            DupsrmError::InvalidRegex(_) => "InvalidRegex",
+            DupsrmError::InvalidGlob(_) => "InvalidGlob",
+            DupsrmError::NotADirectory(_) => "NotADirectory",
+            DupsrmError::Argument(_) => "Argument",
+            DupsrmError::Verification(_) => "Verification",
+            DupsrmError::Removal(_) => "Removal",
+            DupsrmError::Io(_) => "Io",
+        }
+    }
+
+    /// Describe the error as a JSON object with its message and kind
+    pub fn to_json(&self) -> String {
+        serde_json::json!({ "error": self.to_string(), "kind": self.kind() }).to_string()
     }
 }
 
-impl fmt::Display for ArgumentError {
+impl fmt::Display for DupsrmError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.details)
+        match self {
+            DupsrmError::SameDirectory => {
+                write!(
+                    f,
+                    "Reference directory must not be identical to root directory"
+                )
+            }
+            DupsrmError::InvalidRegex(msg) => write!(f, "Invalid regular expression: {}", msg),
+            DupsrmError::InvalidGlob(msg) => write!(f, "Invalid glob pattern: {}", msg),
+            DupsrmError::NotADirectory(path) => {
+                write!(f, "Path {} is not a directory", path.to_str().unwrap())
+            }
+            DupsrmError::Argument(msg)
+            | DupsrmError::Verification(msg)
+            | DupsrmError::Removal(msg) => write!(f, "{}", msg),
+            DupsrmError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for DupsrmError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DupsrmError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for DupsrmError {
+    fn from(err: io::Error) -> DupsrmError {
+        DupsrmError::Io(err)
     }
 }
 
-impl Error for ArgumentError {
-    fn description(&self) -> &str {
-        &self.details
+impl From<globset::Error> for DupsrmError {
+    fn from(err: globset::Error) -> DupsrmError {
+        DupsrmError::InvalidGlob(err.to_string())
     }
 }
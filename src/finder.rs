@@ -0,0 +1,1015 @@
+use super::hasher::{hash_bytes, hash_file, hash_prefix, is_empty_hash, HashAlgorithm};
+use super::index::KeyedState;
+use super::path::{
+    has_excluded_name, is_dangling_symlink, is_excluded, is_file, is_hidden, is_same_file,
+    is_subdirectory,
+};
+use super::strategy::{ByteVerify, FileInfo, MatchStrategy};
+use log::{error, warn};
+use rayon::prelude::*;
+use regex::Regex;
+use std::cmp::Ordering;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io;
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::SystemTime;
+use walkdir::WalkDir;
+
+/// Index of root files by their hash
+pub type Index<'a> = HashMap<&'a Vec<u8>, &'a PathBuf, KeyedState>;
+
+/// Number of leading bytes hashed to rule out files before hashing them whole, 64 KiB
+pub const PARTIAL_HASH_SIZE: u64 = 1 << 16;
+
+/// Size and partial hash of a file
+type PartialKey = (u64, Vec<u8>);
+
+/// Enumerates the policies choosing one root file among several with identical content
+#[derive(Clone)]
+pub enum RootPreference {
+    ShortestPath, //< Shortest path
+    Oldest,       //< Oldest modification time
+    Newest,       //< Newest modification time
+    MostLinked,   //< Highest hard link count
+}
+
+impl RootPreference {
+    /// Check if the candidate root file is preferred over the current one
+    ///
+    /// Ties are broken by the shorter and then the lexicographically smaller path, so the
+    /// choice doesn't depend on the walk order.
+    pub fn prefers(&self, candidate: &Path, current: &Path) -> bool {
+        let ordering = match self {
+            RootPreference::ShortestPath => Ordering::Equal,
+            RootPreference::Oldest => match (modified(candidate), modified(current)) {
+                (Some(candidate), Some(current)) => candidate.cmp(&current),
+                _ => Ordering::Equal,
+            },
+            RootPreference::Newest => match (modified(candidate), modified(current)) {
+                (Some(candidate), Some(current)) => current.cmp(&candidate),
+                _ => Ordering::Equal,
+            },
+            RootPreference::MostLinked => link_count(current).cmp(&link_count(candidate)),
+        };
+        ordering
+            .then_with(|| candidate.as_os_str().len().cmp(&current.as_os_str().len()))
+            .then_with(|| candidate.cmp(current))
+            == Ordering::Less
+    }
+}
+
+/// Enumerates the directory trees whose copies are kept in a deduplication across both trees
+#[derive(Clone)]
+pub enum TreePriority {
+    Reference, //< Keep copies in the reference directory tree
+    Root,      //< Keep copies in the root directory tree
+}
+
+/// Enumerates the policies choosing the copy kept in a deduplication within a single tree
+#[derive(Clone)]
+pub enum KeepPolicy {
+    First,  //< Lexicographically first path
+    Oldest, //< Oldest modification time
+    Newest, //< Newest modification time
+}
+
+impl KeepPolicy {
+    /// Select the file to keep among files with identical content
+    ///
+    /// Ties are broken by the lexicographically smaller path.
+    pub fn select<'a>(&self, paths: &[&'a PathBuf]) -> Option<&'a PathBuf> {
+        paths.iter().copied().min_by(|a, b| {
+            let ordering = match (self, modified(a), modified(b)) {
+                (KeepPolicy::Oldest, Some(a), Some(b)) => a.cmp(&b),
+                (KeepPolicy::Newest, Some(a), Some(b)) => b.cmp(&a),
+                _ => Ordering::Equal,
+            };
+            ordering.then_with(|| a.cmp(b))
+        })
+    }
+}
+
+/// Read the modification time of a file
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Read the number of hard links of a file
+#[cfg(unix)]
+fn link_count(path: &Path) -> u64 {
+    fs::metadata(path).map_or(1, |m| m.nlink())
+}
+
+/// Read the number of hard links of a file
+#[cfg(not(unix))]
+fn link_count(_path: &Path) -> u64 {
+    1
+}
+
+/// Options of a directory walk
+#[derive(Clone, Default)]
+pub struct WalkOptions {
+    pub follow_links: bool, //< Follow symbolic links, skipping link cycles
+    pub parallel: bool,     //< Read directories in parallel with jwalk
+    pub gitignore: bool,    //< Skip files ignored by git, read serially with ignore
+    pub excluded_names: Vec<OsString>, //< Names of directories pruned at any depth below the root
+    pub skip_hidden: bool,  //< Skip dot-prefixed files and directories below the root
+    pub max_depth: Option<usize>, //< Descend at most this many levels below the root
+    pub same_file_system: bool, //< Don't descend into directories on another filesystem than the root
+}
+
+/// Error of a directory walk
+struct WalkError {
+    path: Option<PathBuf>, //< Path of the entry failing to be read
+    is_loop: bool,         //< Entry is a symbolic link to one of its ancestors
+    error: io::Error,      //< Error with the context of the walk
+}
+
+impl From<walkdir::Error> for WalkError {
+    fn from(err: walkdir::Error) -> WalkError {
+        WalkError {
+            path: err.path().map(Path::to_path_buf),
+            is_loop: err.loop_ancestor().is_some(),
+            error: err.into(),
+        }
+    }
+}
+
+impl From<jwalk::Error> for WalkError {
+    fn from(err: jwalk::Error) -> WalkError {
+        WalkError {
+            path: err.path().map(Path::to_path_buf),
+            is_loop: err.loop_ancestor().is_some(),
+            error: err.into(),
+        }
+    }
+}
+
+impl From<ignore::Error> for WalkError {
+    fn from(err: ignore::Error) -> WalkError {
+        // Errors of the ignore crate wrap the context around the underlying error
+        let (mut path, mut is_loop) = (None, false);
+        let mut inner = &err;
+        loop {
+            match inner {
+                ignore::Error::WithPath { path: p, err } => {
+                    path.get_or_insert_with(|| p.clone());
+                    inner = err.as_ref();
+                }
+                ignore::Error::WithDepth { err, .. }
+                | ignore::Error::WithLineNumber { err, .. } => inner = err.as_ref(),
+                ignore::Error::Loop { child, .. } => {
+                    path.get_or_insert_with(|| child.clone());
+                    is_loop = true;
+                    break;
+                }
+                _ => break,
+            }
+        }
+        let kind = err.io_error().map_or(io::ErrorKind::Other, io::Error::kind);
+        WalkError {
+            path,
+            is_loop,
+            error: io::Error::new(kind, err),
+        }
+    }
+}
+
+impl WalkOptions {
+    /// Iterate the files of a directory tree, pruning the excluded directories and names
+    fn files<'a>(
+        &self,
+        dir: &'a Path,
+        excluded: &'a [PathBuf],
+    ) -> Box<dyn Iterator<Item = Result<PathBuf, WalkError>> + 'a> {
+        let names = self.excluded_names.clone();
+        let skip_hidden = self.skip_hidden;
+        if self.gitignore {
+            let pruned = excluded.to_vec();
+            // Ignore files apply outside of git repositories as well
+            return Box::new(
+                ignore::WalkBuilder::new(dir)
+                    .follow_links(self.follow_links)
+                    .hidden(self.skip_hidden)
+                    .max_depth(self.max_depth)
+                    .same_file_system(self.same_file_system)
+                    .require_git(false)
+                    .filter_entry(move |e| {
+                        let is_dir = e.file_type().is_some_and(|file_type| file_type.is_dir());
+                        let named = e.depth() > 0 && is_dir && has_excluded_name(e.path(), &names);
+                        !named && !is_excluded(e.path(), &pruned)
+                    })
+                    .build()
+                    .filter_map(|entry| match entry {
+                        Ok(entry) => entry
+                            .file_type()
+                            .is_some_and(|file_type| file_type.is_file())
+                            .then(|| Ok(entry.into_path())),
+                        Err(err) => Some(Err(err.into())),
+                    }),
+            );
+        }
+        if !self.parallel {
+            return Box::new(
+                WalkDir::new(dir)
+                    .follow_links(self.follow_links)
+                    .max_depth(self.max_depth.unwrap_or(usize::MAX))
+                    .same_file_system(self.same_file_system)
+                    .into_iter()
+                    .filter_entry(move |e| {
+                        let named = e.depth() > 0
+                            && e.file_type().is_dir()
+                            && has_excluded_name(e.path(), &names);
+                        let hidden = skip_hidden && e.depth() > 0 && is_hidden(e.path());
+                        !named && !hidden && !is_excluded(e.path(), excluded)
+                    })
+                    .filter_map(|entry| match entry {
+                        Ok(entry) => is_file(&entry).then(|| Ok(entry.into_path())),
+                        Err(err) => Some(Err(err.into())),
+                    }),
+            );
+        }
+        // jwalk only prunes the children of a directory, so the root is checked up front
+        if is_excluded(dir, excluded) {
+            return Box::new(std::iter::empty());
+        }
+        let pruned = excluded.to_vec();
+        let root = dir.to_path_buf();
+        // jwalk can't stay on a filesystem, so directories on another device are pruned
+        let device = self.same_file_system.then(|| device_id(dir)).flatten();
+        Box::new(
+            jwalk::WalkDir::new(dir)
+                .follow_links(self.follow_links)
+                .max_depth(self.max_depth.unwrap_or(usize::MAX))
+                .skip_hidden(false)
+                .process_read_dir(move |depth, _, _, children| {
+                    // Without a depth the only child is the walk root, which is never pruned by name
+                    let below_root = depth.is_some();
+                    children.retain(|child| {
+                        child.as_ref().map_or(true, |e| {
+                            let cycle = e.path_is_symlink()
+                                && e.file_type().is_dir()
+                                && is_link_cycle(&e.path(), &root);
+                            let named = below_root
+                                && e.file_type().is_dir()
+                                && has_excluded_name(&e.path(), &names);
+                            let hidden = below_root && skip_hidden && is_hidden(&e.path());
+                            let mounted = below_root
+                                && e.file_type().is_dir()
+                                && !is_on_device(&e.path(), device);
+                            !cycle
+                                && !named
+                                && !hidden
+                                && !mounted
+                                && !is_excluded(&e.path(), &pruned)
+                        })
+                    })
+                })
+                .into_iter()
+                .filter_map(|entry| match entry {
+                    Ok(entry) => entry.file_type().is_file().then(|| Ok(entry.path())),
+                    Err(err) => Some(Err(err.into())),
+                }),
+        )
+    }
+}
+
+/// Check if a symbolic link points to one of its ancestor directories up to the walk root
+///
+/// jwalk does not detect link cycles, which are skipped with a warning instead.
+fn is_link_cycle(link: &Path, root: &Path) -> bool {
+    let Ok(target) = link.canonicalize() else {
+        return false;
+    };
+    let cycle = link
+        .ancestors()
+        .skip(1)
+        .take_while(|ancestor| is_subdirectory(ancestor, root))
+        .any(|ancestor| {
+            ancestor
+                .canonicalize()
+                .is_ok_and(|ancestor| ancestor == target)
+        });
+    if cycle {
+        warn!("Skipping {}: symbolic link cycle", link.to_str().unwrap());
+    }
+    cycle
+}
+
+/// Walk a directory tree and collect its files, pruning the excluded directories
+///
+/// Entries failing to be read, e.g. unreadable directories, are skipped with a warning.
+pub fn walk_files(dir: &Path, excluded: &[PathBuf], options: &WalkOptions) -> Vec<PathBuf> {
+    options
+        .files(dir, excluded)
+        .filter_map(|entry| {
+            entry
+                .inspect_err(|err| {
+                    let path = err.path.as_deref().unwrap_or(dir);
+                    warn!("Skipping {}: {}", path.to_str().unwrap(), err.error);
+                })
+                .ok()
+        })
+        .collect()
+}
+
+/// Walk a directory tree and collect its files, aborting on the first entry failing to be read
+///
+/// Symbolic link cycles and followed dangling symbolic links are skipped with a warning.
+pub fn walk_files_strict(
+    dir: &Path,
+    excluded: &[PathBuf],
+    options: &WalkOptions,
+) -> Result<Vec<PathBuf>, io::Error> {
+    let mut files: Vec<PathBuf> = Vec::new();
+    for entry in options.files(dir, excluded) {
+        match entry {
+            Ok(path) => files.push(path),
+            Err(err) if err.is_loop || err.path.as_deref().is_some_and(is_dangling_symlink) => {
+                let path = err.path.as_deref().unwrap_or(dir);
+                warn!("Skipping {}: {}", path.to_str().unwrap(), err.error);
+            }
+            Err(err) => return Err(err.error),
+        }
+    }
+    Ok(files)
+}
+
+/// Check if the modification time of a file lies within the optional bounds
+pub fn is_modified_in_range(
+    path: &Path,
+    after: Option<SystemTime>,
+    before: Option<SystemTime>,
+) -> bool {
+    if after.is_none() && before.is_none() {
+        return true;
+    }
+    match modified(path) {
+        Some(modified) => {
+            after.is_none_or(|after| modified >= after)
+                && before.is_none_or(|before| modified <= before)
+        }
+        None => false,
+    }
+}
+
+/// Read the id of the device holding a file
+#[cfg(unix)]
+pub fn device_id(path: &Path) -> Option<u64> {
+    fs::metadata(path).map(|m| m.dev()).ok()
+}
+
+/// Read the id of the device holding a file, unknown on this platform
+#[cfg(not(unix))]
+pub fn device_id(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Check if a file resides on the given device, like `find -xdev` does
+///
+/// Files are kept if either device is unknown.
+pub fn is_on_device(path: &Path, device: Option<u64>) -> bool {
+    match (device, device_id(path)) {
+        (Some(device), Some(id)) => id == device,
+        _ => true,
+    }
+}
+
+/// Check if the size of a file lies within the optional bounds
+pub fn is_size_in_range(path: &Path, min_size: Option<u64>, max_size: Option<u64>) -> bool {
+    if min_size.is_none() && max_size.is_none() {
+        return true;
+    }
+    match fs::metadata(path) {
+        Ok(metadata) => {
+            min_size.is_none_or(|min| metadata.len() >= min)
+                && max_size.is_none_or(|max| metadata.len() <= max)
+        }
+        Err(_) => false,
+    }
+}
+
+/// Hash files in parallel and pair the hashes with the canonical file paths
+///
+/// Relative paths are canonicalized, absolute paths are taken as canonical already, as the
+/// ones walked below a canonical directory without following symbolic links. Empty files are
+/// skipped unless `keep_empty` is set. Files failing to be hashed or canonicalized are skipped
+/// with a warning.
+pub fn hash_files<F>(
+    files: Vec<PathBuf>,
+    hash_sum: F,
+    algorithm: &HashAlgorithm,
+    keep_empty: bool,
+) -> Vec<(Vec<u8>, PathBuf)>
+where
+    F: Fn(&Path) -> Result<Vec<u8>, io::Error> + Sync,
+{
+    files
+        .into_par_iter()
+        .filter_map(|path| hash_pair(&path, &hash_sum))
+        .filter(|pair| keep_empty || !is_empty_hash(&pair.0, algorithm))
+        .collect()
+}
+
+/// Hash a file and pair the hash with the canonical file path, warning if either fails
+fn hash_pair<F>(path: &Path, hash_sum: &F) -> Option<(Vec<u8>, PathBuf)>
+where
+    F: Fn(&Path) -> Result<Vec<u8>, io::Error>,
+{
+    let canonical = |hash| match path.is_absolute() {
+        true => Ok((hash, path.to_path_buf())),
+        false => Ok((hash, fs::canonicalize(path)?)),
+    };
+    match hash_sum(path).and_then(canonical) {
+        Ok(pair) => Some(pair),
+        Err(err) => {
+            warn!("Skipping file {}: {}", path.to_str().unwrap(), err);
+            None
+        }
+    }
+}
+
+/// Hash a file again and check if it still has the hash recorded when it was matched
+///
+/// Narrows the window in which a file replaced after matching would be processed anyway.
+pub fn is_unchanged<F>(path: &Path, hash: &[u8], hash_sum: F) -> io::Result<bool>
+where
+    F: Fn(&Path) -> Result<Vec<u8>, io::Error>,
+{
+    Ok(hash_sum(path)? == hash)
+}
+
+/// Canonicalize the paths of files in parallel, skipping files failing to be canonicalized with a warning
+///
+/// Needed for paths reached through symbolic links or given by the user, as `hash_files`
+/// takes absolute paths as canonical.
+pub fn canonicalize_files(files: Vec<PathBuf>) -> Vec<PathBuf> {
+    files
+        .into_par_iter()
+        .filter_map(|path| match fs::canonicalize(&path) {
+            Ok(canonical) => Some(canonical),
+            Err(err) => {
+                warn!("Skipping file {}: {}", path.to_str().unwrap(), err);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Keep the root and reference files whose size is found in both trees
+///
+/// A file can only duplicate a file of the other tree with the same size, so files of a size
+/// missing on either side are ruled out without reading them at all. Files failing to be read
+/// are kept for the hashing to report them.
+pub fn plan_candidates(
+    root_files: Vec<PathBuf>,
+    reference_files: Vec<PathBuf>,
+) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let sized = |path: PathBuf| (fs::metadata(&path).map(|m| m.len()).ok(), path);
+    let root_sized: Vec<_> = root_files.into_par_iter().map(sized).collect();
+    let reference_sized: Vec<_> = reference_files.into_par_iter().map(sized).collect();
+    // Number of root and reference files of each size
+    let mut counts: HashMap<u64, (usize, usize)> = HashMap::new();
+    for size in root_sized.iter().filter_map(|pair| pair.0) {
+        counts.entry(size).or_default().0 += 1;
+    }
+    for size in reference_sized.iter().filter_map(|pair| pair.0) {
+        counts.entry(size).or_default().1 += 1;
+    }
+    let select = |sized: Vec<(Option<u64>, PathBuf)>| -> Vec<PathBuf> {
+        sized
+            .into_iter()
+            .filter(|pair| {
+                pair.0.is_none_or(|size| {
+                    counts
+                        .get(&size)
+                        .is_some_and(|(root, reference)| *root > 0 && *reference > 0)
+                })
+            })
+            .map(|pair| pair.1)
+            .collect()
+    };
+    (select(root_sized), select(reference_sized))
+}
+
+/// Keep the root and reference files sharing their size and partial hash with a file of the other tree
+///
+/// Files are planned by their size first, then only the first `PARTIAL_HASH_SIZE` bytes of the
+/// remaining files are hashed with the fast XXH3, so files which can't have a duplicate are
+/// ruled out without reading them whole. Files failing to be read are kept for the full
+/// hashing to report them.
+pub fn prefilter_files(
+    root_files: Vec<PathBuf>,
+    reference_files: Vec<PathBuf>,
+) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let (root_files, reference_files) = plan_candidates(root_files, reference_files);
+    let partial_key = |path: PathBuf| {
+        let key = fs::metadata(&path).and_then(|metadata| {
+            let hash = hash_prefix(&path, &HashAlgorithm::XXH3, PARTIAL_HASH_SIZE)?;
+            Ok((metadata.len(), hash))
+        });
+        (key.ok(), path)
+    };
+    let root_keyed: Vec<_> = root_files.into_par_iter().map(partial_key).collect();
+    let reference_keyed: Vec<_> = reference_files.into_par_iter().map(partial_key).collect();
+    let root_keys: HashSet<&PartialKey> = root_keyed
+        .iter()
+        .filter_map(|pair| pair.0.as_ref())
+        .collect();
+    let reference_keys: HashSet<&PartialKey> = reference_keyed
+        .iter()
+        .filter_map(|pair| pair.0.as_ref())
+        .collect();
+    let select =
+        |keyed: &[(Option<PartialKey>, PathBuf)], keys: &HashSet<&PartialKey>| -> Vec<PathBuf> {
+            keyed
+                .iter()
+                .filter(|pair| pair.0.as_ref().is_none_or(|key| keys.contains(key)))
+                .map(|pair| pair.1.clone())
+                .collect()
+        };
+    (
+        select(&root_keyed, &reference_keys),
+        select(&reference_keyed, &root_keys),
+    )
+}
+
+/// Build the index of root files by their hash
+///
+/// If several root files share a hash, the one chosen by the preference is indexed.
+pub fn build_index<'a>(
+    root_pairs: &'a [(Vec<u8>, PathBuf)],
+    state: KeyedState,
+    preference: &RootPreference,
+) -> Index<'a> {
+    let mut index: Index = HashMap::with_capacity_and_hasher(root_pairs.len(), state);
+    for (hash, path) in root_pairs {
+        match index.entry(hash) {
+            Entry::Vacant(entry) => {
+                entry.insert(path);
+            }
+            Entry::Occupied(mut entry) => {
+                if preference.prefers(path, entry.get()) {
+                    entry.insert(path);
+                }
+            }
+        }
+    }
+    index
+}
+
+/// Build the index of the files to keep in a deduplication within a single tree
+///
+/// Files are grouped by their hash and the policy selects the file kept of each group.
+pub fn build_within_index<'a>(
+    pairs: &'a [(Vec<u8>, PathBuf)],
+    state: KeyedState,
+    keep: &KeepPolicy,
+) -> Index<'a> {
+    let mut groups: HashMap<&Vec<u8>, Vec<&PathBuf>> = HashMap::new();
+    for (hash, path) in pairs {
+        groups.entry(hash).or_default().push(path);
+    }
+    let mut index: Index = HashMap::with_capacity_and_hasher(groups.len(), state);
+    for (hash, paths) in groups {
+        if let Some(path) = keep.select(&paths) {
+            index.insert(hash, path);
+        }
+    }
+    index
+}
+
+/// Build the index of the files to keep across the root and the reference directory trees
+///
+/// For each hash a file of the priority tree is indexed if there is any, otherwise a file
+/// of the other tree. Within a tree the file is chosen by the preference.
+pub fn build_priority_index<'a>(
+    root_pairs: &'a [(Vec<u8>, PathBuf)],
+    reference_pairs: &'a [(Vec<u8>, PathBuf)],
+    state: KeyedState,
+    priority: &TreePriority,
+    preference: &RootPreference,
+) -> Index<'a> {
+    let (primary, secondary) = match priority {
+        TreePriority::Reference => (reference_pairs, root_pairs),
+        TreePriority::Root => (root_pairs, reference_pairs),
+    };
+    let mut index = build_index(primary, state.clone(), preference);
+    for (hash, path) in build_index(secondary, state, preference) {
+        index.entry(hash).or_insert(path);
+    }
+    index
+}
+
+/// Index the file names of root files by their hash
+pub fn index_names(root_pairs: &[(Vec<u8>, PathBuf)]) -> HashMap<&Vec<u8>, HashSet<&OsStr>> {
+    let mut names: HashMap<&Vec<u8>, HashSet<&OsStr>> = HashMap::new();
+    for (hash, path) in root_pairs {
+        if let Some(name) = path.file_name() {
+            names.entry(hash).or_default().insert(name);
+        }
+    }
+    names
+}
+
+/// Check if a root file with the same hash also has the same file name
+pub fn has_same_name(
+    names: &HashMap<&Vec<u8>, HashSet<&OsStr>>,
+    pair: &(Vec<u8>, PathBuf),
+) -> bool {
+    match (names.get(&pair.0), pair.1.file_name()) {
+        (Some(names), Some(name)) => names.contains(name),
+        _ => false,
+    }
+}
+
+/// Select the files whose hash is found in the index, sorted by path
+///
+/// The strategy decides whether a file duplicates the indexed file, e.g. by comparing
+/// their content byte by byte to rule out hash collisions. The indexed files themselves
+/// are never selected.
+pub fn match_duplicates(
+    index: &Index,
+    reference_pairs: &[(Vec<u8>, PathBuf)],
+    strategy: &dyn MatchStrategy,
+) -> Vec<(Vec<u8>, PathBuf)> {
+    let mut duplicate_pairs: Vec<(Vec<u8>, PathBuf)> = reference_pairs
+        .par_iter()
+        .filter(|pair| is_duplicate(index, pair, strategy))
+        .cloned()
+        .collect();
+    duplicate_pairs.sort_by(|a, b| a.1.cmp(&b.1));
+    duplicate_pairs
+}
+
+/// Hash reference files and select those whose hash is found in the index, sorted by path
+///
+/// Files are matched as soon as they are hashed, so only the duplicates are collected
+/// instead of the hashes of all reference files. Files reached at one of the root paths,
+/// e.g. through followed symbolic links, are skipped like files failing to be hashed.
+pub fn hash_match_duplicates<F>(
+    index: &Index,
+    files: Vec<PathBuf>,
+    hash_sum: F,
+    algorithm: &HashAlgorithm,
+    keep_empty: bool,
+    strategy: &dyn MatchStrategy,
+    root_paths: &HashSet<&PathBuf>,
+) -> Vec<(Vec<u8>, PathBuf)>
+where
+    F: Fn(&Path) -> Result<Vec<u8>, io::Error> + Sync,
+{
+    let mut duplicate_pairs: Vec<(Vec<u8>, PathBuf)> = files
+        .into_par_iter()
+        .filter_map(|path| hash_pair(&path, &hash_sum))
+        .filter(|pair| keep_empty || !is_empty_hash(&pair.0, algorithm))
+        .filter(|pair| !root_paths.contains(&pair.1))
+        .filter(|pair| is_duplicate(index, pair, strategy))
+        .collect();
+    duplicate_pairs.sort_by(|a, b| a.1.cmp(&b.1));
+    // A file reached through several paths is hashed more than once
+    duplicate_pairs.dedup_by(|a, b| a.1 == b.1);
+    duplicate_pairs
+}
+
+/// Check if a reference file duplicates the indexed root file with the same hash
+fn is_duplicate(index: &Index, pair: &(Vec<u8>, PathBuf), strategy: &dyn MatchStrategy) -> bool {
+    matching_root(index, pair, strategy).is_some()
+}
+
+/// Look up the indexed root file with the same hash as a reference file, if it is a duplicate
+fn matching_root<'a>(
+    index: &Index<'a>,
+    pair: &(Vec<u8>, PathBuf),
+    strategy: &dyn MatchStrategy,
+) -> Option<&'a PathBuf> {
+    match index.get_key_value(&pair.0) {
+        Some((root_hash, root_path)) if !is_same_file(root_path, &pair.1) => {
+            let reference = FileInfo {
+                path: &pair.1,
+                hash: &pair.0,
+            };
+            let candidate_root = FileInfo {
+                path: root_path,
+                hash: root_hash,
+            };
+            match strategy.is_duplicate(&reference, &candidate_root) {
+                Ok(duplicate) => duplicate.then_some(*root_path),
+                Err(err) => {
+                    error!(
+                        "Verifying file {} failed: {}",
+                        pair.1.to_str().unwrap(),
+                        err
+                    );
+                    None
+                }
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Reference file duplicating a root file
+#[derive(Clone, Debug, PartialEq)]
+pub struct Duplicate {
+    pub reference: PathBuf,  //< Canonical path of the reference file
+    pub root_match: PathBuf, //< Canonical path of the root file it duplicates
+    pub hash: Vec<u8>,       //< Hash of both files
+    pub size: u64,           //< Size of both files in bytes, 0 if it can't be read
+}
+
+impl Duplicate {
+    /// Describe a matched reference file by its hash and the root file it duplicates
+    ///
+    /// The size is read from the reference file, so it must still exist.
+    pub fn new(hash: Vec<u8>, reference: PathBuf, root_match: PathBuf) -> Duplicate {
+        let size = fs::metadata(&reference).map_or(0, |metadata| metadata.len());
+        Duplicate {
+            reference,
+            root_match,
+            hash,
+            size,
+        }
+    }
+}
+
+/// Find the files in the reference directory tree that duplicate files in the root directory tree
+///
+/// Reference files can be filtered by a regular expression matched against their path
+/// relative to the reference directory, as on the command line. The duplicates are sorted by the path of the reference file. No file is removed.
+///
+/// ```no_run
+/// use dupsrm::finder::find_duplicates;
+/// use dupsrm::hasher::HashAlgorithm;
+/// use std::path::Path;
+///
+/// let duplicates = find_duplicates(
+///     Path::new("archive"),
+///     Path::new("downloads"),
+///     &HashAlgorithm::SHA2_256,
+///     None,
+/// )?;
+/// for duplicate in &duplicates {
+///     println!(
+///         "{} duplicates {} ({} bytes)",
+///         duplicate.reference.display(),
+///         duplicate.root_match.display(),
+///         duplicate.size
+///     );
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn find_duplicates(
+    root: &Path,
+    reference: &Path,
+    algorithm: &HashAlgorithm,
+    regex: Option<&Regex>,
+) -> Result<Vec<Duplicate>, io::Error> {
+    let mut duplicates: Vec<Duplicate> = Vec::new();
+    for_each_duplicate(root, reference, algorithm, regex, |duplicate| {
+        duplicates.push(duplicate.clone())
+    })?;
+    duplicates.sort_by(|a, b| a.reference.cmp(&b.reference));
+    Ok(duplicates)
+}
+
+/// Call back for each file in the reference directory tree duplicating a file in the root directory tree
+///
+/// Duplicates are reported as soon as they are found, e.g. to update a user interface live,
+/// and in no particular order. Reference files are hashed in parallel, but the callback is
+/// only invoked on the calling thread, one duplicate at a time, so it needs neither be `Send`
+/// nor `Sync`. The regular expression is matched as by [`find_duplicates`]. No file is removed.
+pub fn for_each_duplicate<F>(
+    root: &Path,
+    reference: &Path,
+    algorithm: &HashAlgorithm,
+    regex: Option<&Regex>,
+    mut callback: F,
+) -> Result<(), io::Error>
+where
+    F: FnMut(&Duplicate),
+{
+    let hash_sum = |path: &Path| hash_file(path, algorithm);
+    let root = root.canonicalize()?;
+    let reference = reference.canonicalize()?;
+
+    let root_files = walk_files(
+        &root,
+        std::slice::from_ref(&reference),
+        &WalkOptions::default(),
+    );
+    let root_pairs = hash_files(root_files, hash_sum, algorithm, false);
+
+    let reference_files: Vec<PathBuf> = walk_files(&reference, &[], &WalkOptions::default())
+        .into_iter()
+        .filter(|path| {
+            let relative = path.strip_prefix(&reference).unwrap_or(path);
+            regex.is_none_or(|re| re.is_match(relative.to_str().unwrap_or("")))
+        })
+        .collect();
+    let index = build_index(
+        &root_pairs,
+        KeyedState::random(),
+        &RootPreference::ShortestPath,
+    );
+    let root_paths: HashSet<&PathBuf> = root_pairs.iter().map(|pair| &pair.1).collect();
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            reference_files
+                .into_par_iter()
+                .filter_map(|path| hash_pair(&path, &hash_sum))
+                .filter(|pair| !is_empty_hash(&pair.0, algorithm))
+                .filter(|pair| !root_paths.contains(&pair.1))
+                .for_each_with(sender, |sender, pair| {
+                    if let Some(root_match) = matching_root(&index, &pair, &ByteVerify) {
+                        // The receiver outlives the hashing thread
+                        let _ = sender.send(Duplicate::new(pair.0, pair.1, root_match.clone()));
+                    }
+                });
+        });
+        for duplicate in receiver {
+            callback(&duplicate);
+        }
+    });
+    Ok(())
+}
+
+/// Reference directory duplicating a root directory file by file
+#[derive(Clone, Debug, PartialEq)]
+pub struct DuplicateDirectory {
+    pub reference: PathBuf,    //< Canonical path of the reference directory
+    pub root_match: PathBuf,   //< Canonical path of the root directory it duplicates
+    pub files: Vec<Duplicate>, //< Files of the reference directory, matching the root files at the same relative paths
+}
+
+/// Iterate the directories holding a file, from its parent up to but excluding `base`
+fn subdirectories<'a>(path: &'a Path, base: &'a Path) -> impl Iterator<Item = &'a Path> {
+    path.ancestors()
+        .skip(1)
+        .take_while(move |dir| *dir != base && dir.starts_with(base))
+}
+
+/// Hash each directory below `base` by the sorted relative paths and hashes of the files in its tree
+///
+/// Directories holding one of the `unhashed` files, e.g. a file skipped by a filter, get no
+/// hash, so they never match as a whole. `base` itself isn't hashed.
+pub fn hash_directories(
+    pairs: &[(Vec<u8>, PathBuf)],
+    unhashed: &[PathBuf],
+    base: &Path,
+    algorithm: &HashAlgorithm,
+) -> HashMap<PathBuf, Vec<u8>> {
+    let incomplete: HashSet<&Path> = unhashed
+        .iter()
+        .flat_map(|path| subdirectories(path, base))
+        .collect();
+    let mut entries: HashMap<&Path, Vec<(&Path, &[u8])>> = HashMap::new();
+    for (hash, path) in pairs {
+        for dir in subdirectories(path, base).filter(|dir| !incomplete.contains(dir)) {
+            let relative = path.strip_prefix(dir).unwrap();
+            entries.entry(dir).or_default().push((relative, hash));
+        }
+    }
+    entries
+        .into_par_iter()
+        .filter_map(|(dir, mut files)| {
+            files.sort();
+            // Paths never contain NUL characters and hashes have a fixed length
+            let mut bytes = Vec::new();
+            for (path, hash) in files {
+                bytes.extend_from_slice(path.as_os_str().as_encoded_bytes());
+                bytes.push(0);
+                bytes.extend_from_slice(hash);
+            }
+            let hash = hash_bytes(&bytes, algorithm);
+            Some((dir.to_path_buf(), hash))
+        })
+        .collect()
+}
+
+/// Find an entry of a directory tree besides the given files, e.g. a file pruned while walking
+fn uncompared_entry(dir: &Path, files: &HashSet<&Path>) -> io::Result<Option<PathBuf>> {
+    for entry in WalkDir::new(dir) {
+        let entry = entry?;
+        if !entry.file_type().is_dir() && !files.contains(entry.path()) {
+            return Ok(Some(entry.into_path()));
+        }
+    }
+    Ok(None)
+}
+
+/// Match the reference directories whose files all have the same relative paths and content in a root directory
+///
+/// Of nested matching reference directories only the outermost passing the checks below is
+/// selected, so a skipped directory leaves its matching subdirectories selected. A directory
+/// holding anything besides the reference files on disk, e.g. a hidden or excluded file, is
+/// skipped with a warning, as it couldn't be removed as a unit. The strategy checks each file
+/// against its root counterpart, e.g. byte by byte, and a directory with a file failing the
+/// check is skipped with a warning too. The directories are sorted by path.
+pub fn match_directories(
+    root_hashes: &HashMap<PathBuf, Vec<u8>>,
+    reference_hashes: &HashMap<PathBuf, Vec<u8>>,
+    reference_pairs: &[(Vec<u8>, PathBuf)],
+    strategy: &dyn MatchStrategy,
+) -> Vec<DuplicateDirectory> {
+    // The first path in order, so runs pick the same root directory
+    let mut index: HashMap<&Vec<u8>, &PathBuf> = HashMap::with_capacity(root_hashes.len());
+    for (dir, hash) in root_hashes {
+        index
+            .entry(hash)
+            .and_modify(|indexed| *indexed = (*indexed).min(dir))
+            .or_insert(dir);
+    }
+    let mut matched: Vec<(&PathBuf, &PathBuf)> = reference_hashes
+        .iter()
+        .filter_map(|(dir, hash)| index.get(hash).map(|root| (dir, *root)))
+        .collect();
+    // Sorted by path, a directory comes right before its subdirectories
+    matched.sort();
+    let passed: Vec<DuplicateDirectory> = matched
+        .into_par_iter()
+        .filter_map(|(reference, root)| {
+            let files: Vec<Duplicate> = reference_pairs
+                .iter()
+                .filter(|pair| pair.1.starts_with(reference))
+                .map(|(hash, path)| {
+                    let root_path = root.join(path.strip_prefix(reference).unwrap());
+                    Duplicate::new(hash.clone(), path.clone(), root_path)
+                })
+                .collect();
+            let listed: HashSet<&Path> =
+                files.iter().map(|file| file.reference.as_path()).collect();
+            match uncompared_entry(reference, &listed) {
+                Ok(None) => {}
+                Ok(Some(path)) => {
+                    warn!(
+                        "Skipping directory {}: {} wasn't compared",
+                        reference.to_str().unwrap(),
+                        path.to_str().unwrap()
+                    );
+                    return None;
+                }
+                Err(err) => {
+                    warn!(
+                        "Skipping directory {}: {}",
+                        reference.to_str().unwrap(),
+                        err
+                    );
+                    return None;
+                }
+            }
+            let verified = files.iter().all(|file| {
+                let file_info = FileInfo {
+                    path: &file.reference,
+                    hash: &file.hash,
+                };
+                let root_info = FileInfo {
+                    path: &file.root_match,
+                    hash: &file.hash,
+                };
+                strategy
+                    .is_duplicate(&file_info, &root_info)
+                    .unwrap_or_else(|err| {
+                        error!(
+                            "Verifying file {} failed: {}",
+                            file.reference.to_str().unwrap(),
+                            err
+                        );
+                        false
+                    })
+            });
+            if !verified {
+                warn!(
+                    "Skipping directory {}: its files differ from {}",
+                    reference.to_str().unwrap(),
+                    root.to_str().unwrap()
+                );
+                return None;
+            }
+            Some(DuplicateDirectory {
+                reference: reference.clone(),
+                root_match: root.clone(),
+                files,
+            })
+        })
+        .collect();
+    // A subdirectory is only selected if its skipped parent leaves it outermost
+    let mut outermost: Vec<DuplicateDirectory> = Vec::new();
+    for dir in passed {
+        if outermost
+            .last()
+            .is_none_or(|last| !dir.reference.starts_with(&last.reference))
+        {
+            outermost.push(dir);
+        }
+    }
+    outermost
+}
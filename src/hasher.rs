@@ -1,254 +1,413 @@
 use blake::{self, Blake};
+use blake2::{Blake2b512, Blake2s256};
+use log::warn;
 use md5::{self, Md5};
+use memmap2::Mmap;
 use ripemd::{self, Ripemd160};
 use sha1::{self, Sha1};
-use sha2::{Digest, Sha256};
+use sha2::{Digest, Sha256, Sha384, Sha512};
 use sha3::{self, Sha3_256};
+use std::cell::RefCell;
+use std::fmt;
 use std::fs::File;
-use std::io::{self, BufReader, Read};
+use std::io::{self, Read};
 use std::path::Path;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::thread;
+use std::time::Duration;
 use whirlpool::{self, Whirlpool};
+use xxhash_rust::xxh3::{xxh3_64, Xxh3};
+
+/// Delay before the first retry of a failed read, doubled for each further retry
+pub const RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Size of the buffer used to read files while hashing, 64 KiB
+pub const READ_BUFFER_SIZE: usize = 1 << 16;
 
 /// Enumerates the hash algorithm
 #[derive(Clone)]
 pub enum HashAlgorithm {
-    SHA2_256,  //< SHA256
-    SHA3_256,  //< SHA3-256
-    SHA1,      //< SHA1
-    MD5,       //< MD5
-    WHIRLPOOL, //< Whirlpool
-    RIPEMD160, //< RIPEMD-160
-    BLAKE256,  //< BLAKE-256
+    SHA2_256,   //< SHA256
+    SHA2_384,   //< SHA384
+    SHA2_512,   //< SHA512
+    SHA3_256,   //< SHA3-256
+    SHA1,       //< SHA1
+    MD5,        //< MD5
+    WHIRLPOOL,  //< Whirlpool
+    RIPEMD160,  //< RIPEMD-160
+    BLAKE256,   //< BLAKE-256
+    BLAKE2B512, //< BLAKE2b-512
+    BLAKE2S256, //< BLAKE2s-256
+    XXH3,       //< XXH3 64-bit, not cryptographic
+    CRC32,      //< CRC-32 checksum, not cryptographic and colliding often
+}
+
+impl HashAlgorithm {
+    /// All hash algorithms
+    pub const VARIANTS: [HashAlgorithm; 13] = [
+        HashAlgorithm::SHA2_256,
+        HashAlgorithm::SHA2_384,
+        HashAlgorithm::SHA2_512,
+        HashAlgorithm::SHA3_256,
+        HashAlgorithm::SHA1,
+        HashAlgorithm::MD5,
+        HashAlgorithm::WHIRLPOOL,
+        HashAlgorithm::RIPEMD160,
+        HashAlgorithm::BLAKE256,
+        HashAlgorithm::BLAKE2B512,
+        HashAlgorithm::BLAKE2S256,
+        HashAlgorithm::XXH3,
+        HashAlgorithm::CRC32,
+    ];
+
+    /// Canonical name of the hash algorithm, e.g. `SHA2-256`
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashAlgorithm::SHA2_256 => "SHA2-256",
+            HashAlgorithm::SHA2_384 => "SHA2-384",
+            HashAlgorithm::SHA2_512 => "SHA2-512",
+            HashAlgorithm::SHA3_256 => "SHA3-256",
+            HashAlgorithm::SHA1 => "SHA1",
+            HashAlgorithm::MD5 => "MD5",
+            HashAlgorithm::WHIRLPOOL => "WHIRLPOOL",
+            HashAlgorithm::RIPEMD160 => "RIPEMD-160",
+            HashAlgorithm::BLAKE256 => "BLAKE-256",
+            HashAlgorithm::BLAKE2B512 => "BLAKE2b-512",
+            HashAlgorithm::BLAKE2S256 => "BLAKE2s-256",
+            HashAlgorithm::XXH3 => "XXH3",
+            HashAlgorithm::CRC32 => "CRC32",
+        }
+    }
+
+    /// Check if hash collisions are practically impossible to produce, even on purpose
+    pub fn is_cryptographic(&self) -> bool {
+        !matches!(self, HashAlgorithm::XXH3 | HashAlgorithm::CRC32)
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = String;
+
+    /// Parse the canonical name of a hash algorithm
+    fn from_str(name: &str) -> Result<HashAlgorithm, String> {
+        HashAlgorithm::VARIANTS
+            .into_iter()
+            .find(|algorithm| algorithm.name() == name)
+            .ok_or_else(|| {
+                let names: Vec<&str> = HashAlgorithm::VARIANTS.iter().map(|a| a.name()).collect();
+                format!(
+                    "unknown hash algorithm '{}', expected one of {}",
+                    name,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+thread_local! {
+    /// Read buffer of the thread, reused across the files it hashes instead of allocated per file
+    static READ_BUFFER: RefCell<Vec<u8>> = RefCell::new(vec![0; READ_BUFFER_SIZE]);
+}
+
+/// Read the content of a reader in chunks into the buffer of the thread and pass them to `update`
+fn read_chunks<R: Read, F: FnMut(&[u8])>(mut reader: R, mut update: F) -> Result<(), io::Error> {
+    READ_BUFFER.with_borrow_mut(|buffer| loop {
+        let count = reader.read(buffer)?;
+        if count == 0 {
+            return Ok(());
+        }
+        update(&buffer[..count]);
+    })
+}
+
+/// Hash the content of a reader with a RustCrypto digest and return its hash value
+fn digest_reader<D: Digest, R: Read>(reader: R) -> Result<Vec<u8>, io::Error> {
+    let mut hasher = D::new();
+    read_chunks(reader, |chunk| hasher.update(chunk))?;
+    Ok(hasher.finalize().to_vec())
+}
+
+/// Hash a file with a RustCrypto digest and return its hash value
+fn digest_file<D: Digest>(path: &Path) -> Result<Vec<u8>, io::Error> {
+    digest_reader::<D, _>(File::open(path)?)
 }
 
 /// Hash a file and return its sha256 hash value
 pub fn sha256sum(path: &Path) -> Result<Vec<u8>, io::Error> {
-    let file = match File::open(path) {
-        Err(err) => return Err(err),
-        Ok(file) => file,
-    };
-
-    let mut reader = BufReader::new(file);
-    let digest = {
-        let mut hasher = Sha256::new();
-        let mut buffer = [0; 4098];
-        loop {
-            let count = reader.read(&mut buffer)?;
-            if count == 0 {
-                break;
-            }
-            hasher.update(&buffer[..count]);
-        }
-        hasher.finalize()
-    };
-    Ok(digest.to_vec())
-    // Ok(HEXLOWER.decode(HEXLOWER.encode(digest.as_ref())).unwrap())
+    digest_file::<Sha256>(path)
+}
+
+/// Hash a file and return its SHA384 hash value
+pub fn sha384sum(path: &Path) -> Result<Vec<u8>, io::Error> {
+    digest_file::<Sha384>(path)
+}
+
+/// Hash a file and return its SHA512 hash value
+pub fn sha512sum(path: &Path) -> Result<Vec<u8>, io::Error> {
+    digest_file::<Sha512>(path)
 }
 
 /// Hash a file and return its SHA3-256 hash value
 pub fn sha3_256sum(path: &Path) -> Result<Vec<u8>, io::Error> {
-    let file = match File::open(path) {
-        Err(err) => return Err(err),
-        Ok(file) => file,
-    };
-
-    let mut reader = BufReader::new(file);
-    let digest = {
-        let mut hasher = Sha3_256::new();
-        let mut buffer = [0; 4098];
-        loop {
-            let count = reader.read(&mut buffer)?;
-            if count == 0 {
-                break;
-            }
-            hasher.update(&buffer[..count]);
-        }
-        hasher.finalize()
-    };
-    Ok(digest.to_vec())
+    digest_file::<Sha3_256>(path)
 }
 
 /// Hash a file and return its SHA1 hash value
 pub fn sha1sum(path: &Path) -> Result<Vec<u8>, io::Error> {
-    let file = match File::open(path) {
-        Err(err) => return Err(err),
-        Ok(file) => file,
-    };
-
-    let mut reader = BufReader::new(file);
-    let digest = {
-        let mut hasher = Sha1::new();
-        let mut buffer = [0; 4098];
-        loop {
-            let count = reader.read(&mut buffer)?;
-            if count == 0 {
-                break;
-            }
-            hasher.update(&buffer[..count]);
-        }
-        hasher.finalize()
-    };
-    Ok(digest.to_vec())
+    digest_file::<Sha1>(path)
 }
 
 /// Hash a file and return its MD5 hash value
 pub fn md5sum(path: &Path) -> Result<Vec<u8>, io::Error> {
-    let file = match File::open(path) {
-        Err(err) => return Err(err),
-        Ok(file) => file,
-    };
-
-    let mut reader = BufReader::new(file);
-    let digest = {
-        let mut hasher = Md5::new();
-        let mut buffer = [0; 4098];
-        loop {
-            let count = reader.read(&mut buffer)?;
-            if count == 0 {
-                break;
-            }
-            hasher.update(&buffer[..count]);
-        }
-        hasher.finalize()
-    };
-    Ok(digest.to_vec())
+    digest_file::<Md5>(path)
 }
 
 /// Hash a file and return its Whirlpool hash value
 pub fn whirlpool_sum(path: &Path) -> Result<Vec<u8>, io::Error> {
-    let file = match File::open(path) {
-        Err(err) => return Err(err),
-        Ok(file) => file,
-    };
-
-    let mut reader = BufReader::new(file);
-    let digest = {
-        let mut hasher = Whirlpool::new();
-        let mut buffer = [0; 4098];
-        loop {
-            let count = reader.read(&mut buffer)?;
-            if count == 0 {
-                break;
-            }
-            hasher.update(&buffer[..count]);
-        }
-        hasher.finalize()
-    };
-    Ok(digest.to_vec())
+    digest_file::<Whirlpool>(path)
 }
 
-/// Hash a file and return its BLAKE-256 hash value
-pub fn blake256_sum(path: &Path) -> Result<Vec<u8>, io::Error> {
-    let file = match File::open(path) {
-        Err(err) => return Err(err),
-        Ok(file) => file,
-    };
-
-    let mut reader = BufReader::new(file);
+/// Hash the content of a reader with BLAKE-256 and return its hash value
+fn blake256_reader<R: Read>(reader: R) -> Result<Vec<u8>, io::Error> {
     let mut hasher = Blake::new(256).unwrap();
-    {
-        let mut buffer = [0; 4098];
-        loop {
-            let count = reader.read(&mut buffer)?;
-            if count == 0 {
-                break;
-            }
-            hasher.update(&buffer[..count]);
-        }
-    };
+    read_chunks(reader, |chunk| hasher.update(chunk))?;
     let mut digest = [0; 32];
     hasher.finalise(&mut digest);
     Ok(digest.to_vec())
 }
 
+/// Hash a file and return its BLAKE-256 hash value
+pub fn blake256_sum(path: &Path) -> Result<Vec<u8>, io::Error> {
+    blake256_reader(File::open(path)?)
+}
+
+/// Hash a file and return its BLAKE2b-512 hash value
+pub fn blake2b512_sum(path: &Path) -> Result<Vec<u8>, io::Error> {
+    digest_file::<Blake2b512>(path)
+}
+
+/// Hash a file and return its BLAKE2s-256 hash value
+pub fn blake2s256_sum(path: &Path) -> Result<Vec<u8>, io::Error> {
+    digest_file::<Blake2s256>(path)
+}
+
+/// Hash the content of a reader with XXH3 and return its big-endian 64-bit hash value
+fn xxh3_reader<R: Read>(reader: R) -> Result<Vec<u8>, io::Error> {
+    let mut hasher = Xxh3::new();
+    read_chunks(reader, |chunk| hasher.update(chunk))?;
+    Ok(hasher.digest().to_be_bytes().to_vec())
+}
+
+/// Hash a file and return its XXH3 hash value
+pub fn xxh3_sum(path: &Path) -> Result<Vec<u8>, io::Error> {
+    xxh3_reader(File::open(path)?)
+}
+
+/// Checksum the content of a reader with CRC-32 and return its big-endian 32-bit value
+fn crc32_reader<R: Read>(reader: R) -> Result<Vec<u8>, io::Error> {
+    let mut hasher = crc32fast::Hasher::new();
+    read_chunks(reader, |chunk| hasher.update(chunk))?;
+    Ok(hasher.finalize().to_be_bytes().to_vec())
+}
+
+/// Checksum a file and return its CRC-32 value
+///
+/// CRC-32 collides too often to identify duplicates on its own. It's a cheap first pass, which
+/// must be followed by the byte-by-byte verification of the matches.
+pub fn crc32_sum(path: &Path) -> Result<Vec<u8>, io::Error> {
+    crc32_reader(File::open(path)?)
+}
+
 /// Hash a file and return its RIPEMD-160 hash value
 pub fn ripemd160_sum(path: &Path) -> Result<Vec<u8>, io::Error> {
-    let file = match File::open(path) {
-        Err(err) => return Err(err),
-        Ok(file) => file,
-    };
-
-    let mut reader = BufReader::new(file);
-    let digest = {
-        let mut hasher = Ripemd160::new();
-        let mut buffer = [0; 4098];
-        loop {
-            let count = reader.read(&mut buffer)?;
-            if count == 0 {
-                break;
+    digest_file::<Ripemd160>(path)
+}
+
+/// Hash the content of a reader with the given algorithm and return its hash value
+pub fn hash_reader<R: Read>(reader: R, algorithm: &HashAlgorithm) -> Result<Vec<u8>, io::Error> {
+    match algorithm {
+        HashAlgorithm::SHA2_256 => digest_reader::<Sha256, _>(reader),
+        HashAlgorithm::SHA2_384 => digest_reader::<Sha384, _>(reader),
+        HashAlgorithm::SHA2_512 => digest_reader::<Sha512, _>(reader),
+        HashAlgorithm::SHA3_256 => digest_reader::<Sha3_256, _>(reader),
+        HashAlgorithm::SHA1 => digest_reader::<Sha1, _>(reader),
+        HashAlgorithm::MD5 => digest_reader::<Md5, _>(reader),
+        HashAlgorithm::WHIRLPOOL => digest_reader::<Whirlpool, _>(reader),
+        HashAlgorithm::RIPEMD160 => digest_reader::<Ripemd160, _>(reader),
+        HashAlgorithm::BLAKE256 => blake256_reader(reader),
+        HashAlgorithm::BLAKE2B512 => digest_reader::<Blake2b512, _>(reader),
+        HashAlgorithm::BLAKE2S256 => digest_reader::<Blake2s256, _>(reader),
+        HashAlgorithm::XXH3 => xxh3_reader(reader),
+        HashAlgorithm::CRC32 => crc32_reader(reader),
+    }
+}
+
+/// Call `read` on a file again after a failure, up to `retries` times with a doubling delay
+///
+/// Meant for transient errors, e.g. timeouts of network filesystems. Errors which can't go away
+/// by themselves, a missing file or a denied permission, are returned at once.
+pub fn retrying<T, F>(path: &Path, retries: u32, mut read: F) -> Result<T, io::Error>
+where
+    F: FnMut(&Path) -> Result<T, io::Error>,
+{
+    let mut delay = RETRY_DELAY;
+    for _ in 0..retries {
+        match read(path) {
+            Err(err)
+                if !matches!(
+                    err.kind(),
+                    io::ErrorKind::NotFound | io::ErrorKind::PermissionDenied
+                ) =>
+            {
+                warn!(
+                    "Reading {} failed: {}, retrying in {:?}",
+                    path.to_str().unwrap(),
+                    err,
+                    delay
+                );
+                thread::sleep(delay);
+                delay *= 2;
             }
-            hasher.update(&buffer[..count]);
+            result => return result,
         }
-        hasher.finalize()
-    };
-    digest.as_slice();
-    Ok(digest.to_vec())
+    }
+    read(path)
+}
+
+/// Hash a file with the given algorithm and return its hash value
+pub fn hash_file(path: &Path, algorithm: &HashAlgorithm) -> Result<Vec<u8>, io::Error> {
+    hash_reader(File::open(path)?, algorithm)
 }
 
-/// Checks if the string equals the empty hash
-pub fn is_empty_hash(hash: &Vec<u8>, algorithm: &HashAlgorithm) -> bool {
+/// Hash the first `length` bytes of a file with the given algorithm and return its hash value
+pub fn hash_prefix(
+    path: &Path,
+    algorithm: &HashAlgorithm,
+    length: u64,
+) -> Result<Vec<u8>, io::Error> {
+    hash_reader(File::open(path)?.take(length), algorithm)
+}
+
+/// Hash a byte slice with the given algorithm in a single update and return its hash value
+pub fn hash_bytes(bytes: &[u8], algorithm: &HashAlgorithm) -> Vec<u8> {
     match algorithm {
-        // e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855
-        HashAlgorithm::SHA2_256 => {
-            hash.to_owned()
-                == vec![
-                    0xe3, 0xb0, 0xc4, 0x42, 0x98, 0xfc, 0x1c, 0x14, 0x9a, 0xfb, 0xf4, 0xc8, 0x99,
-                    0x6f, 0xb9, 0x24, 0x27, 0xae, 0x41, 0xe4, 0x64, 0x9b, 0x93, 0x4c, 0xa4, 0x95,
-                    0x99, 0x1b, 0x78, 0x52, 0xb8, 0x55,
-                ]
-        }
-        // a7ffc6f8bf1ed76651c14756a061d662f580ff4de43b49fa82d80a4b80f8434a
-        HashAlgorithm::SHA3_256 => {
-            hash.to_owned()
-                == vec![
-                    0xa7, 0xff, 0xc6, 0xf8, 0xbf, 0x1e, 0xd7, 0x66, 0x51, 0xc1, 0x47, 0x56, 0xa0,
-                    0x61, 0xd6, 0x62, 0xf5, 0x80, 0xff, 0x4d, 0xe4, 0x3b, 0x49, 0xfa, 0x82, 0xd8,
-                    0x0a, 0x4b, 0x80, 0xf8, 0x43, 0x4a,
-                ]
-        }
-        // da39a3ee5e6b4b0d3255bfef95601890afd80709
-        HashAlgorithm::SHA1 => {
-            hash.to_owned()
-                == vec![
-                    0xda, 0x39, 0xa3, 0xee, 0x5e, 0x6b, 0x4b, 0x0d, 0x32, 0x55, 0xbf, 0xef, 0x95,
-                    0x60, 0x18, 0x90, 0xaf, 0xd8, 0x07, 0x09,
-                ]
-        }
-        // d41d8cd98f00b204e9800998ecf8427e
-        HashAlgorithm::MD5 => {
-            hash.to_owned()
-                == vec![
-                    0xd4, 0x1d, 0x8c, 0xd9, 0x8f, 0x00, 0xb2, 0x04, 0xe9, 0x80, 0x09, 0x98, 0xec,
-                    0xf8, 0x42, 0x7e,
-                ]
-        }
-        // 19fa61d75522a4669b44e39c1d2e1726c530232130d407f89afee0964997f7a73e83be698b288febcf88e3e03c4f0757ea8964e59b63d93708b138cc42a66eb3
-        HashAlgorithm::WHIRLPOOL => {
-            hash.to_owned()
-                == vec![
-                    0x19, 0xfa, 0x61, 0xd7, 0x55, 0x22, 0xa4, 0x66, 0x9b, 0x44, 0xe3, 0x9c, 0x1d,
-                    0x2e, 0x17, 0x26, 0xc5, 0x30, 0x23, 0x21, 0x30, 0xd4, 0x07, 0xf8, 0x9a, 0xfe,
-                    0xe0, 0x96, 0x49, 0x97, 0xf7, 0xa7, 0x3e, 0x83, 0xbe, 0x69, 0x8b, 0x28, 0x8f,
-                    0xeb, 0xcf, 0x88, 0xe3, 0xe0, 0x3c, 0x4f, 0x07, 0x57, 0xea, 0x89, 0x64, 0xe5,
-                    0x9b, 0x63, 0xd9, 0x37, 0x08, 0xb1, 0x38, 0xcc, 0x42, 0xa6, 0x6e, 0xb3,
-                ]
-        }
-        // 9c1185a5c5e9fc54612808977ee8f548b2258d31
-        HashAlgorithm::RIPEMD160 => {
-            hash.to_owned()
-                == vec![
-                    0x9c, 0x11, 0x85, 0xa5, 0xc5, 0xe9, 0xfc, 0x54, 0x61, 0x28, 0x08, 0x97, 0x7e,
-                    0xe8, 0xf5, 0x48, 0xb2, 0x25, 0x8d, 0x31,
-                ]
-        }
-        // 716f6e863f744b9ac22c97ec7b76ea5f5908bc5b2f67c61510bfc4751384ea7a
+        HashAlgorithm::SHA2_256 => Sha256::digest(bytes).to_vec(),
+        HashAlgorithm::SHA2_384 => Sha384::digest(bytes).to_vec(),
+        HashAlgorithm::SHA2_512 => Sha512::digest(bytes).to_vec(),
+        HashAlgorithm::SHA3_256 => Sha3_256::digest(bytes).to_vec(),
+        HashAlgorithm::SHA1 => Sha1::digest(bytes).to_vec(),
+        HashAlgorithm::MD5 => Md5::digest(bytes).to_vec(),
+        HashAlgorithm::WHIRLPOOL => Whirlpool::digest(bytes).to_vec(),
+        HashAlgorithm::RIPEMD160 => Ripemd160::digest(bytes).to_vec(),
         HashAlgorithm::BLAKE256 => {
-            hash.to_owned()
-                == vec![
-                    0x71, 0x6f, 0x6e, 0x86, 0x3f, 0x74, 0x4b, 0x9a, 0xc2, 0x2c, 0x97, 0xec, 0x7b,
-                    0x76, 0xea, 0x5f, 0x59, 0x08, 0xbc, 0x5b, 0x2f, 0x67, 0xc6, 0x15, 0x10, 0xbf,
-                    0xc4, 0x75, 0x13, 0x84, 0xea, 0x7a,
-                ]
+            let mut hasher = Blake::new(256).unwrap();
+            hasher.update(bytes);
+            let mut digest = [0; 32];
+            hasher.finalise(&mut digest);
+            digest.to_vec()
+        }
+        HashAlgorithm::BLAKE2B512 => Blake2b512::digest(bytes).to_vec(),
+        HashAlgorithm::BLAKE2S256 => Blake2s256::digest(bytes).to_vec(),
+        HashAlgorithm::XXH3 => xxh3_64(bytes).to_be_bytes().to_vec(),
+        HashAlgorithm::CRC32 => crc32fast::hash(bytes).to_be_bytes().to_vec(),
+    }
+}
+
+/// Hash a file with the given algorithm, memory-mapping it if it has at least `threshold` bytes
+///
+/// A mapped file is hashed in a single update instead of a loop of buffered reads. Smaller
+/// files, and files failing to be mapped, are read with a buffer.
+pub fn hash_file_mapped(
+    path: &Path,
+    algorithm: &HashAlgorithm,
+    threshold: u64,
+) -> Result<Vec<u8>, io::Error> {
+    let file = File::open(path)?;
+    if file.metadata()?.len() >= threshold {
+        // SAFETY: the mapping is only read while it lives; a file truncated meanwhile by another
+        // process may raise SIGBUS, a risk accepted by choosing to map files
+        if let Ok(mmap) = unsafe { Mmap::map(&file) } {
+            return Ok(hash_bytes(&mmap, algorithm));
         }
     }
+    hash_reader(file, algorithm)
+}
+
+/// Read a trusted hash value stored in an extended attribute of a file
+///
+/// The file content is not read. The attribute value is used as is, so all files must carry
+/// the value in the same encoding.
+#[cfg(unix)]
+pub fn xattr_hash(path: &Path, name: &str) -> Result<Vec<u8>, io::Error> {
+    xattr::get(path, name)?
+        .filter(|value| !value.is_empty())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Extended attribute {} not found", name),
+            )
+        })
+}
+
+/// Read a trusted hash value stored in an extended attribute of a file
+///
+/// Extended attributes are only supported on Unix platforms.
+#[cfg(not(unix))]
+pub fn xattr_hash(_path: &Path, _name: &str) -> Result<Vec<u8>, io::Error> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Extended attributes are not supported on this platform",
+    ))
+}
+
+/// Encode a hash as a lowercase hexadecimal string
+pub fn to_hex(hash: &[u8]) -> String {
+    hash.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Decode a hash from a hexadecimal string
+pub fn from_hex(hex: &str) -> Result<Vec<u8>, io::Error> {
+    // Checked up front, as parsing the pairs would accept a sign like `+f`
+    if !hex.len().is_multiple_of(2) || !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid hexadecimal hash: {}", hex),
+        ));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&hex[i..i + 2], 16).map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid hexadecimal hash {}: {}", hex, err),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Hash value of empty input with the given algorithm, computed once for all algorithms
+pub fn empty_hash(algorithm: &HashAlgorithm) -> &'static [u8] {
+    static EMPTY_HASHES: OnceLock<Vec<Vec<u8>>> = OnceLock::new();
+    // The variants are listed in declaration order, so an algorithm's discriminant indexes them
+    let empty_hashes = EMPTY_HASHES.get_or_init(|| {
+        HashAlgorithm::VARIANTS
+            .iter()
+            .map(|algorithm| hash_bytes(&[], algorithm))
+            .collect()
+    });
+    &empty_hashes[algorithm.clone() as usize]
+}
+
+/// Check if the hash value is the hash of empty input
+pub fn is_empty_hash(hash: &[u8], algorithm: &HashAlgorithm) -> bool {
+    hash == empty_hash(algorithm)
 }
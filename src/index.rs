@@ -0,0 +1,45 @@
+use siphasher::sip::SipHasher13;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+/// SipHash-1-3 state with a configurable key used to bucket the hash index
+///
+/// The key only affects the bucketing of the index, never which files are matched.
+#[derive(Clone)]
+pub struct KeyedState {
+    k0: u64,
+    k1: u64,
+}
+
+impl KeyedState {
+    /// Create a state from a user supplied 128 bit key
+    pub fn new(key: u128) -> KeyedState {
+        KeyedState {
+            k0: (key >> 64) as u64,
+            k1: key as u64,
+        }
+    }
+
+    /// Create a state from a randomly seeded key
+    pub fn random() -> KeyedState {
+        let state = RandomState::new();
+        KeyedState {
+            k0: state.build_hasher().finish(),
+            k1: state.hash_one(0xd0b5_u64),
+        }
+    }
+}
+
+impl BuildHasher for KeyedState {
+    type Hasher = SipHasher13;
+
+    fn build_hasher(&self) -> SipHasher13 {
+        SipHasher13::new_with_keys(self.k0, self.k1)
+    }
+}
+
+/// Parse a 128 bit index key given as up to 32 hexadecimal digits
+pub fn parse_index_key(key: &str) -> Result<u128, String> {
+    u128::from_str_radix(key, 16)
+        .map_err(|err| format!("expected up to 32 hexadecimal digits: {}", err))
+}
@@ -1,5 +1,16 @@
+pub mod action;
+pub mod cache;
 pub mod cli;
+pub mod compare;
 pub mod error;
+pub mod finder;
 pub mod hasher;
+pub mod index;
 pub mod logger;
+pub mod manifest;
 pub mod path;
+pub mod privilege;
+pub mod progress;
+pub mod report;
+pub mod strategy;
+pub mod timing;
@@ -17,3 +17,22 @@ impl log::Log for ConsoleLogger {
 
     fn flush(&self) {}
 }
+
+/// Logger leaving out error records, which are reported by the JSON error object instead
+pub struct QuietErrors<L>(pub L);
+
+impl<L: log::Log> log::Log for QuietErrors<L> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() != Level::Error && self.0.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            self.0.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.0.flush();
+    }
+}
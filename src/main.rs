@@ -16,166 +16,1529 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use clap::Parser;
-use dupsrm::cli::Cli;
-use dupsrm::error::ArgumentError;
+use dupsrm::action::{confirm, remove_batched, remove_empty_dirs, Action};
+use dupsrm::cache::HashCache;
+use dupsrm::cli::{AlgorithmChoice, Cli, ErrorFormat, OutputFormat};
+use dupsrm::error::DupsrmError;
+use dupsrm::finder::{
+    build_index, build_priority_index, build_within_index, canonicalize_files, has_same_name,
+    hash_directories, hash_files, hash_match_duplicates, index_names, is_modified_in_range,
+    is_size_in_range, is_unchanged, match_directories, match_duplicates, prefilter_files,
+    walk_files, walk_files_strict, Duplicate, DuplicateDirectory, Index, WalkOptions,
+};
 use dupsrm::hasher::{
-    blake256_sum, is_empty_hash, md5sum, ripemd160_sum, sha1sum, sha256sum, sha3_256sum,
-    whirlpool_sum, HashAlgorithm,
+    empty_hash, hash_file, hash_file_mapped, hash_prefix, is_empty_hash, retrying, to_hex,
+    xattr_hash, READ_BUFFER_SIZE,
+};
+use dupsrm::index::KeyedState;
+use dupsrm::logger::{QuietErrors, CONSOLE_LOGGER};
+use dupsrm::manifest::{read_manifest, verify_manifest, write_manifest};
+use dupsrm::path::{
+    build_glob_set, build_regex, canonicalize_output, check_distinct, is_glob_selected,
+    is_subdirectory, read_path_list, resolve, write_path_list,
+};
+use dupsrm::privilege::{is_elevated, may_remove};
+use dupsrm::progress::{advancing, progress_bar};
+use dupsrm::report::{
+    format_bytes, group_by_hash, merge_reports, read_json, write_csv, write_dot, write_json,
+    write_json_summary, write_log_entry, DuplicateRecord, RemovalLogEntry, RunSummary,
 };
-use dupsrm::logger::CONSOLE_LOGGER;
-use dupsrm::path::{is_file, is_subdirectory};
+use dupsrm::strategy::{ByteVerify, ExactHash, MatchStrategy, Prefix};
+use dupsrm::timing::{counting_bytes, Timings};
 use env_logger::Env;
+use globset::GlobSet;
+use indicatif::ProgressBar;
 use log::Level;
 use log::{debug, error, info, warn};
 use rayon::prelude::*;
 use regex::Regex;
-use std::collections::HashMap;
-use std::fs;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Write};
 use std::path::{Path, PathBuf};
-use walkdir::{DirEntry, WalkDir};
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Initialize logger
-    env_logger::Builder::from_env(Env::default().default_filter_or(Level::Info.as_str()))
-        .format_timestamp(None)
-        .init();
-    let _ = log::set_logger(&CONSOLE_LOGGER);
+/// Save the hash cache, if any, to its file
+fn save_cache(cache: &Option<HashCache>, cache_path: &Option<PathBuf>) -> io::Result<()> {
+    match (cache, cache_path) {
+        (Some(cache), Some(cache_path)) => cache.save(cache_path),
+        _ => Ok(()),
+    }
+}
 
+fn main() -> Result<(), DupsrmError> {
     // Parse command line arguments
-    let args = Cli::parse();
-    let root_dir = match Path::new(&args.root_dir).canonicalize() {
-        Ok(dir) => dir,
-        Err(err) => {
-            error!("Error checking root path: {}", err);
-            return Err(err.into());
-        }
+    let mut args = Cli::parse();
+
+    // Initialize logger, leaving errors to the JSON error object if one is written
+    let logger =
+        env_logger::Builder::from_env(Env::default().default_filter_or(Level::Info.as_str()))
+            .format_timestamp(None)
+            .build();
+    let max_level = logger.filter();
+    let logger: Box<dyn log::Log> = match args.error_format {
+        ErrorFormat::Json => Box::new(QuietErrors(logger)),
+        ErrorFormat::Text => Box::new(logger),
     };
-    let reference_dir = match Path::new(&args.reference_dir).canonicalize() {
-        Ok(dir) => dir,
-        Err(err) => {
-            error!("Error checking reference path: {}", err);
-            return Err(err.into());
+    log::set_boxed_logger(logger).expect("the logger is initialized once");
+    log::set_max_level(max_level);
+    let _ = log::set_logger(&CONSOLE_LOGGER);
+
+    args.resolve_paths();
+    args.resolve_hash_algorithm();
+    args.dry_run |= args.count_only;
+    // Empty files are part of the directories they are in
+    args.keep_empty |= args.dirs;
+    let error_format = args.error_format.clone();
+    // Code 1 tells duplicates were found with --exit-code, so errors exit with code 2 then
+    let error_code = if args.exit_code { 2 } else { 1 };
+    match run(args) {
+        // A single object, so tools embedding dupsrm don't have to parse the message
+        Err(err) if error_format == ErrorFormat::Json => {
+            eprintln!("{}", err.to_json());
+            process::exit(error_code);
         }
-    };
-    if root_dir.is_dir() {
-        info!("Root directory: {}", root_dir.to_str().unwrap());
-    } else {
-        warn!(
-            "Root path {} should be a directory",
-            root_dir.to_str().unwrap()
-        );
+        Err(err) if error_code != 1 => {
+            eprintln!("Error: {:?}", err);
+            process::exit(error_code);
+        }
+        result => result,
     }
-    if reference_dir.is_dir() {
-        info!("Reference directory: {}", reference_dir.to_str().unwrap());
-    } else {
+}
+
+/// Hashes files as given by the arguments
+struct FileHasher<'a> {
+    args: &'a Cli,
+    algorithm_name: String, //< Name of the hash algorithm, stored with the cached hashes
+    cache: Option<HashCache>, //< Cache of the content hashes, if any
+    hashed_bytes: AtomicU64, //< Size of the hashed files, only read if the throughput is reported
+}
+
+impl<'a> FileHasher<'a> {
+    fn new(args: &'a Cli) -> io::Result<FileHasher<'a>> {
+        let cache = match &args.cache {
+            Some(cache_path) => Some(HashCache::load(cache_path)?),
+            None => None,
+        };
+        Ok(FileHasher {
+            args,
+            algorithm_name: args.hash_algorithm.to_string(),
+            cache,
+            hashed_bytes: AtomicU64::new(0),
+        })
+    }
+
+    /// Hash the content of a file, retrying transient read failures
+    fn content_hash(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let args = self.args;
+        retrying(path, args.io_retries, |path| {
+            match (args.hash_bytes, args.mmap_threshold) {
+                (Some(length), _) => hash_prefix(path, &args.hash_algorithm, length),
+                (None, Some(threshold)) => hash_file_mapped(path, &args.hash_algorithm, threshold),
+                (None, None) => hash_file(path, &args.hash_algorithm),
+            }
+        })
+    }
+
+    /// Hash a file by its trusted attribute, its cached hash or its content
+    fn hash(&self, path: &Path) -> io::Result<Vec<u8>> {
+        match (&self.args.trust_attr, &self.cache) {
+            (Some(name), _) => xattr_hash(path, name),
+            (None, Some(cache)) => {
+                cache.hash(path, &self.algorithm_name, |path| self.content_hash(path))
+            }
+            (None, None) => self.content_hash(path),
+        }
+    }
+
+    /// Hash files advancing the progress bar, counting their size if the throughput is reported
+    fn hashing<'b>(
+        &'b self,
+        progress: &'b ProgressBar,
+    ) -> impl Fn(&Path) -> io::Result<Vec<u8>> + Sync + 'b {
+        let hashed_bytes = self.args.verbose.then_some(&self.hashed_bytes);
+        advancing(
+            counting_bytes(move |path: &Path| self.hash(path), hashed_bytes),
+            progress,
+        )
+    }
+
+    /// Save the hash cache, if any, to its file
+    fn save_cache(&self) -> io::Result<()> {
+        save_cache(&self.cache, &self.args.cache)
+    }
+}
+
+/// Filters selecting the walked reference files
+struct ReferenceFilters<'a> {
+    regex: Option<Regex>, //< Matched against the path relative to the reference directory
+    reference_dir: Option<&'a Path>, //< Reference directory, if the files weren't listed
+    include: Option<GlobSet>, //< Patterns of the files to include
+    exclude: Option<GlobSet>, //< Patterns of the files to exclude
+    since: Option<SystemTime>, //< Modification time of the marker file
+    modified_after: Option<SystemTime>, //< Earliest modification time of the files
+    modified_before: Option<SystemTime>, //< Latest modification time of the files
+}
+
+/// Numbers of files left after the phases of a run
+struct RunCounts {
+    walked: usize,      //< Reference files walked
+    filtered: usize,    //< Reference files passing the filters
+    hashed: usize,      //< Reference files left to hash
+    root_hashed: usize, //< Root files hashed
+}
+
+/// Find and process the duplicates as given by the arguments
+fn run(args: Cli) -> Result<(), DupsrmError> {
+    // Keep stdout and stderr free of anything but the requested output and problems
+    if args.print0 || args.exit_code || args.count_only {
+        log::set_max_level(log::LevelFilter::Warn);
+    }
+    let start_time = SystemTime::now();
+
+    // Size the global thread pool, e.g. fewer threads are faster on a slow disk
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(args.threads)
+        .build_global()
+        .map_err(io::Error::other)?;
+
+    // Merge reports of previous runs
+    if !args.merge_reports.is_empty() {
+        return merge_previous_reports(&args.merge_reports);
+    }
+
+    // Compile the regular expressions before anything is walked
+    let regex = compile_regex(&args.regex, args.regex_ignore_case)?;
+    let root_regex = compile_regex(&args.root_regex, false)?;
+
+    check_privileges(&args)?;
+    let (reference_dir, reference_list) = read_reference(&args)?;
+
+    // Choose hash function
+    match args.algorithm_choice {
+        AlgorithmChoice::Auto => info!("Hash algorithm: {} (auto)", args.hash_algorithm),
+        AlgorithmChoice::Given(_) => info!("Hash algorithm: {}", args.hash_algorithm),
+    }
+    if let Some(length) = args.hash_bytes {
         warn!(
-            "Reference path {} should be a directory",
-            reference_dir.to_str().unwrap()
+            "Only hashing the first {} of files, files of the same size differing after them are taken as duplicates",
+            format_bytes(length)
         );
     }
-    if reference_dir.eq(&root_dir) {
-        error!("Reference directory must not be identical to root directory");
-        return Err(ArgumentError::new(
-            "Reference directory must not be identical to root directory",
-        ));
+    let hasher = FileHasher::new(&args)?;
+
+    // Verify the reference directory against a manifest
+    if let Some(manifest_path) = &args.verify_manifest {
+        return verify_reference(&args, manifest_path, reference_dir.as_deref(), &hasher);
     }
 
+    let root_dirs = check_directories(&args, reference_dir.as_deref(), reference_list.as_deref())?;
+
     // Formulate regex
     match &args.regex {
         Some(str) => info!("regex: \'{}\'", str),
         None => info!("regex: \"\""),
     }
+    if let Some(str) = &args.root_regex {
+        info!("root regex: \'{}\'", str);
+    }
 
-    let regex: Option<Regex> = args
-        .regex
-        .map(|re_str| Regex::new(re_str.as_str()).unwrap());
+    // Ages are measured from the start of the run
+    let filters = ReferenceFilters {
+        regex,
+        reference_dir: reference_dir.as_deref(),
+        include: build_glob_set(&args.include)?,
+        exclude: build_glob_set(&args.exclude)?,
+        since: read_marker_time(args.since.as_deref()),
+        modified_after: args.newer_than.and_then(|age| start_time.checked_sub(age)),
+        modified_before: args.older_than.and_then(|age| start_time.checked_sub(age)),
+    };
 
-    // Choose hash function
-    let hash_sum = match args.hash_algorithm {
-        HashAlgorithm::SHA2_256 => |path: &Path| sha256sum(path),
-        HashAlgorithm::SHA3_256 => |path: &Path| sha3_256sum(path),
-        HashAlgorithm::SHA1 => |path: &Path| sha1sum(path),
-        HashAlgorithm::MD5 => |path: &Path| md5sum(path),
-        HashAlgorithm::WHIRLPOOL => |path: &Path| whirlpool_sum(path),
-        HashAlgorithm::RIPEMD160 => |path: &Path| ripemd160_sum(path),
-        HashAlgorithm::BLAKE256 => |path: &Path| blake256_sum(path),
-    };
-
-    // Calculate list of hashes for the root directory tree
-    let root_dirs: Vec<DirEntry> = WalkDir::new(root_dir.clone())
-        .into_iter()
-        .filter_entry(|e| !is_subdirectory(&e.clone().into_path(), &reference_dir))
-        .filter_map(|v| v.ok())
+    // Exclude the paths dupsrm writes into from both walks to avoid reprocessing its own
+    // output, and the reference directory from the root walk
+    let excluded_dirs = output_paths(&args);
+    let mut root_excluded_dirs = excluded_dirs.clone();
+    root_excluded_dirs.extend(reference_dir.clone());
+    let mut reference_excluded_dirs = excluded_dirs.clone();
+    if let Some(reference_dir) = &reference_dir {
+        reference_excluded_dirs.extend(
+            root_dirs
+                .iter()
+                .filter(|root_dir| is_subdirectory(root_dir, reference_dir))
+                .cloned(),
+        );
+    }
+    // Listed reference files are not matched with themselves
+    let listed_files: HashSet<PathBuf> = reference_list
+        .iter()
+        .flatten()
+        .filter_map(|path| path.canonicalize().ok())
         .collect();
-    let root_files: Vec<DirEntry> = root_dirs.into_par_iter().filter(is_file).collect();
 
-    let root_pairs: Vec<(Vec<u8>, PathBuf)> = root_files
-        .into_par_iter()
-        .map(|e| {
-            (
-                hash_sum(e.path()).unwrap(),
-                fs::canonicalize(e.path()).unwrap(),
-            )
-        })
-        .filter(|pair| !is_empty_hash(&pair.0, &args.hash_algorithm))
-        .collect();
+    let mut timings = Timings::new();
+    let follow_reference_links = args.follow_symlinks || args.follow_reference_symlinks;
+    let walk_options = WalkOptions {
+        follow_links: follow_reference_links,
+        parallel: args.parallel_walk,
+        gitignore: args.respect_gitignore,
+        excluded_names: args.exclude_dir.clone(),
+        skip_hidden: args.ignore_hidden,
+        max_depth: args.reference_max_depth,
+        same_file_system: args.one_file_system,
+    };
+    let root_walk_options = WalkOptions {
+        follow_links: args.follow_symlinks || args.follow_root_symlinks,
+        max_depth: args.root_max_depth,
+        same_file_system: false,
+        ..walk_options.clone()
+    };
+
+    // List the files of the reference directory tree
+    let reference_files = walk_reference(
+        &args,
+        reference_dir.as_deref(),
+        reference_list.as_deref(),
+        &reference_excluded_dirs,
+        &excluded_dirs,
+        &walk_options,
+    )?;
+    let walked_count = reference_files.len();
+    // Directories holding a file which isn't hashed can't match as a whole
+    let walked_files = match (args.dirs, follow_reference_links) {
+        (true, true) => canonicalize_files(reference_files.clone()),
+        (true, false) => reference_files.clone(),
+        (false, _) => Vec::new(),
+    };
+    let mut reference_files = filter_reference(&args, reference_files, &filters);
+    // Walked paths are canonical unless reached through links or below listed paths
+    if follow_reference_links || reference_list.is_some() {
+        reference_files = canonicalize_files(reference_files);
+    }
+    timings.lap("walk");
+    let reference_count = reference_files.len();
+    if reference_count == 0 && walked_count > 0 {
+        warn!("No reference files matched the given filters");
+    } else if reference_count == 0 {
+        warn!("No reference files found");
+    }
+
+    // Only features looking at every reference hash need the reference pairs to be collected,
+    // otherwise each reference file is matched against the root index as soon as it is hashed
+    let collect_reference = args.within
+        || args.dirs
+        || args.priority.is_some()
+        || args.write_checksums.is_some()
+        || args.dot.is_some();
+    // Files whose size and leading bytes match no file of the other tree are never hashed whole,
+    // unless their hashes are looked at, come from elsewhere, cover only the first bytes
+    // or the memory of the whole root index is reported
+    let prefilter = !collect_reference
+        && args.cache.is_none()
+        && args.trust_attr.is_none()
+        && args.hash_bytes.is_none()
+        && !args.report_memory;
 
-    // Calculate list of hashes for the reference directory tree
-    let reference_dirs: Vec<DirEntry> = WalkDir::new(reference_dir)
+    // Calculate list of hashes for the root directory tree, along with whether the prefilter may
+    // have left root files with content unhashed, which are hashable but match no reference file
+    let (root_pairs, prefiltered_content) = match &args.root_checksums {
+        // Precomputed root hashes replace the walk of the root directory trees
+        Some(checksums_path) => {
+            let root_pairs = load_root_checksums(
+                &args,
+                checksums_path,
+                &root_dirs,
+                root_regex.as_ref(),
+                &listed_files,
+            )?;
+            timings.lap("load-root");
+            (root_pairs, false)
+        }
+        None => {
+            let root_files = walk_root(
+                &args,
+                &root_dirs,
+                &root_excluded_dirs,
+                &root_walk_options,
+                root_regex.as_ref(),
+                &listed_files,
+            )?;
+            timings.lap("walk");
+            let prefiltered_content = prefilter
+                && root_files.par_iter().any(|path| {
+                    args.keep_empty || fs::metadata(path).is_ok_and(|metadata| metadata.len() > 0)
+                });
+            let root_files = match prefilter {
+                true => {
+                    let files = root_files.len() + reference_files.len();
+                    let (root_files, candidate_files) =
+                        prefilter_files(root_files, std::mem::take(&mut reference_files));
+                    reference_files = candidate_files;
+                    debug!(
+                        "Prefilter: {} of {} files left to hash",
+                        root_files.len() + reference_files.len(),
+                        files
+                    );
+                    timings.lap("prefilter");
+                    root_files
+                }
+                false => root_files,
+            };
+            let progress = progress_bar(root_files.len(), "Hashing root", !args.no_progress);
+            let root_pairs = hash_files(
+                root_files,
+                hasher.hashing(&progress),
+                &args.hash_algorithm,
+                args.keep_empty,
+            );
+            progress.finish_and_clear();
+            timings.lap("hash-root");
+            (root_pairs, prefiltered_content)
+        }
+    };
+    // The arguments may have been swapped if there's nothing to match reference files against
+    if root_pairs.is_empty() && !prefiltered_content && !args.within {
+        warn!("Root directory contains no hashable files, nothing can be matched");
+    }
+
+    // Followed symbolic links may reach a file through several paths or lead into a root tree
+    let root_paths: HashSet<&PathBuf> = root_pairs.iter().map(|pair| &pair.1).collect();
+    let hashed_count = reference_files.len();
+    let progress = progress_bar(
+        reference_files.len(),
+        "Hashing reference",
+        !args.no_progress,
+    );
+    let (reference_pairs, streamed_files) = if collect_reference {
+        let mut reference_pairs = hash_files(
+            reference_files,
+            hasher.hashing(&progress),
+            &args.hash_algorithm,
+            args.keep_empty,
+        );
+        progress.finish_and_clear();
+        reference_pairs.retain(|pair| !root_paths.contains(&pair.1));
+        reference_pairs.sort_by(|a, b| a.1.cmp(&b.1));
+        reference_pairs.dedup_by(|a, b| a.1 == b.1);
+        hasher.save_cache()?;
+        timings.lap("hash-reference");
+        (reference_pairs, Vec::new())
+    } else {
+        (Vec::new(), reference_files)
+    };
+
+    write_hash_outputs(
+        &args,
+        &root_pairs,
+        &reference_pairs,
+        reference_dir.as_deref(),
+    )?;
+
+    // Find duplicates
+    debug!("Check for duplicates");
+    timings.skip();
+    let root_hashmap = build_root_index(&args, &root_pairs, &reference_pairs);
+    let strategy = choose_strategy(&args);
+    let mut duplicate_dirs: Vec<DuplicateDirectory> = Vec::new();
+    let mut duplicate_pairs = if args.dirs {
+        // Whole directories replace the files matched one by one
+        duplicate_dirs = match_reference_dirs(
+            &args,
+            reference_dir
+                .as_deref()
+                .expect("reference directory is required"),
+            &root_dirs,
+            &root_pairs,
+            &reference_pairs,
+            walked_files,
+            strategy.as_ref(),
+        );
+        duplicate_dirs
+            .iter()
+            .flat_map(|dir| &dir.files)
+            .map(|file| (file.hash.clone(), file.reference.clone()))
+            .collect()
+    } else if collect_reference {
+        match &args.priority {
+            // Across both trees every copy but the indexed one is a duplicate
+            Some(_) => {
+                let all_pairs: Vec<(Vec<u8>, PathBuf)> =
+                    root_pairs.iter().chain(&reference_pairs).cloned().collect();
+                match_duplicates(&root_hashmap, &all_pairs, strategy.as_ref())
+            }
+            None => match_duplicates(&root_hashmap, &reference_pairs, strategy.as_ref()),
+        }
+    } else {
+        let duplicate_pairs = hash_match_duplicates(
+            &root_hashmap,
+            streamed_files,
+            hasher.hashing(&progress),
+            &args.hash_algorithm,
+            args.keep_empty,
+            strategy.as_ref(),
+            &root_paths,
+        );
+        progress.finish_and_clear();
+        hasher.save_cache()?;
+        timings.lap("hash-reference");
+        duplicate_pairs
+    };
+    retain_selected(&args, &mut duplicate_pairs, &root_pairs);
+    timings.lap("match");
+    info!("{:?}", duplicate_pairs);
+
+    if args.report_memory {
+        report_memory(&root_hashmap);
+    }
+
+    check_delete_limits(&args, duplicate_pairs.len(), reference_count)?;
+
+    if args.touch_marker {
+        // Anchor the marker at the start of the run so files modified meanwhile are reprocessed
+        if let Some(marker) = &args.since {
+            fs::OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(marker)?
+                .set_modified(start_time)?;
+        }
+    }
+
+    // Read the sizes before the files are gone
+    let duplicates: Vec<Duplicate> = match args.dirs {
+        true => duplicate_dirs
+            .iter()
+            .flat_map(|dir| dir.files.iter().cloned())
+            .collect(),
+        false => duplicate_pairs
+            .par_iter()
+            .map(|pair| {
+                Duplicate::new(
+                    pair.0.clone(),
+                    pair.1.clone(),
+                    root_hashmap[&pair.0].clone(),
+                )
+            })
+            .collect(),
+    };
+
+    timings.skip();
+    let action = choose_action(&args, reference_dir.as_deref());
+    // Ask before touching any file, the listed files are exactly the ones processed
+    if args.confirm_once && !duplicates.is_empty() && !confirm_duplicates(&duplicates, &action)? {
+        info!("Aborted, no file was touched");
+        return Ok(());
+    }
+
+    // Duplicates skipped as their content changed are kept on purpose, not failures
+    let (removed, changed_count) = if duplicates.is_empty() {
+        info!("No duplicates found");
+        (Vec::new(), 0)
+    } else if !args.dry_run {
+        apply_action(&args, &hasher, &duplicates, &action)?
+    } else {
+        list_duplicates(&args, &duplicate_dirs, &duplicates)?;
+        (vec![false; duplicates.len()], 0)
+    };
+    // Directories go as a unit once all their files are gone
+    if !args.dry_run && !duplicate_dirs.is_empty() {
+        remove_duplicate_dirs(&duplicate_dirs, &duplicates, &removed);
+    }
+    timings.lap("act");
+
+    let counts = RunCounts {
+        walked: walked_count,
+        filtered: reference_count,
+        hashed: hashed_count,
+        root_hashed: root_pairs.len(),
+    };
+    report_results(&args, &counts, &duplicates, &removed, &action)?;
+    report_timings(&args, &timings, &hasher);
+
+    // Signal found duplicates by the exit code alone, like `grep -q`
+    if args.exit_code && !duplicates.is_empty() {
+        process::exit(1);
+    }
+
+    // Exit with code 1 if processing a duplicate failed, or 2 if the run kept going anyway
+    let failures = removed.iter().filter(|removed| !**removed).count() - changed_count;
+    if !args.dry_run && failures > 0 {
+        let msg = format!("{} {} files failed", action.present_participle(), failures);
+        if args.keep_going {
+            error!("{}", msg);
+            process::exit(2);
+        }
+        return Err(DupsrmError::Removal(msg));
+    }
+
+    Ok(())
+}
+
+/// Merge the JSON reports of previous runs into a summary
+fn merge_previous_reports(report_paths: &[PathBuf]) -> Result<(), DupsrmError> {
+    let reports = report_paths
+        .iter()
+        .map(|path| read_json(path))
+        .collect::<Result<Vec<_>, io::Error>>()?;
+    let merged = merge_reports(&reports);
+    info!(
+        "Merged {} reports: {} files removed, freed {}",
+        merged.reports,
+        merged.removed,
+        format_bytes(merged.bytes_freed)
+    );
+    if let Some((hash, count)) = merged.most_duplicated {
+        info!("Most duplicated content: {} ({} files)", hash, count);
+    }
+    Ok(())
+}
+
+/// Compile an optional regular expression, logging a failure
+fn compile_regex(
+    pattern: &Option<String>,
+    ignore_case: bool,
+) -> Result<Option<Regex>, DupsrmError> {
+    pattern
+        .as_deref()
+        .map(|pattern| build_regex(pattern, ignore_case))
+        .transpose()
+        .inspect_err(|err| error!("{}", err))
+}
+
+/// Guard against removing files with elevated privileges
+fn check_privileges(args: &Cli) -> Result<(), DupsrmError> {
+    let elevated = is_elevated();
+    if elevated {
+        warn!("!!! Running with root privileges, files are removed with unrestricted access !!!");
+    }
+    if !may_remove(elevated, args.allow_root, args.dry_run) {
+        error!("Refusing to remove files as root, pass --allow-root or use --dry-run");
+        return Err(DupsrmError::Argument(
+            "Refusing to remove files as root, pass --allow-root or use --dry-run".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Resolve the paths of a list against the base directory, if any
+fn resolve_list(paths: Vec<PathBuf>, base_dir: Option<&Path>) -> Vec<PathBuf> {
+    paths
         .into_iter()
-        .filter_map(|v| v.ok())
-        .collect();
-    let reference_files: Vec<DirEntry> = reference_dirs.into_par_iter().filter(is_file).collect();
+        .map(|path| match base_dir {
+            Some(base_dir) => resolve(&path, base_dir),
+            None => path,
+        })
+        .collect()
+}
+
+/// Read the reference files from a list, or from stdin if the reference argument is `-`,
+/// otherwise canonicalize the reference directory
+fn read_reference(args: &Cli) -> Result<(Option<PathBuf>, Option<Vec<PathBuf>>), DupsrmError> {
+    let base_dir = args.base_dir.as_deref();
+    let reference_arg = args.reference_dir.as_ref();
+    if args.confirm_once && reference_arg.is_some_and(|arg| arg.as_os_str() == "-") {
+        let msg = "--confirm-once reads the answer from stdin, pass the reference list with --reference-from";
+        error!("{}", msg);
+        return Err(DupsrmError::Argument(msg.to_string()));
+    }
+    if args.dirs && reference_arg.is_some_and(|arg| arg.as_os_str() == "-") {
+        let msg = "--dirs matches the directories below a reference directory, not a list of files";
+        error!("{}", msg);
+        return Err(DupsrmError::Argument(msg.to_string()));
+    }
+    let reference_list: Option<Vec<PathBuf>> = match &args.reference_from {
+        Some(list_path) => Some(resolve_list(
+            read_path_list(BufReader::new(File::open(list_path)?), true)?,
+            base_dir,
+        )),
+        None if reference_arg.is_some_and(|arg| arg.as_os_str() == "-") => Some(resolve_list(
+            read_path_list(io::stdin().lock(), args.null)?,
+            base_dir,
+        )),
+        None => None,
+    };
+    let reference_dir: Option<PathBuf> = match reference_list {
+        Some(_) => None,
+        None => match reference_arg
+            .expect("reference directory is required")
+            .canonicalize()
+        {
+            Ok(dir) => Some(dir),
+            Err(err) => {
+                error!("Error checking reference path: {}", err);
+                return Err(err.into());
+            }
+        },
+    };
+    Ok((reference_dir, reference_list))
+}
 
-    let reference_files: Vec<DirEntry> = reference_files
+/// Verify the reference directory against a manifest, rewriting the manifest if asked to
+fn verify_reference(
+    args: &Cli,
+    manifest_path: &Path,
+    reference_dir: Option<&Path>,
+    hasher: &FileHasher,
+) -> Result<(), DupsrmError> {
+    let reference_dir = reference_dir.ok_or_else(|| {
+        DupsrmError::Argument("Verifying a manifest requires a reference directory".to_string())
+    })?;
+    let manifest = read_manifest(manifest_path, reference_dir)?;
+    let excluded = vec![manifest_path.canonicalize()?];
+    let report = verify_manifest(&manifest, reference_dir, &excluded, |path: &Path| {
+        hasher.hash(path)
+    });
+    hasher.save_cache()?;
+    report
+        .missing
+        .iter()
+        .for_each(|path| warn!("Missing {}", path.to_str().unwrap()));
+    report
+        .changed
+        .iter()
+        .for_each(|path| warn!("Changed {}", path.to_str().unwrap()));
+    report
+        .new
+        .iter()
+        .for_each(|path| warn!("New {}", path.to_str().unwrap()));
+    report
+        .unreadable
+        .iter()
+        .for_each(|(path, err)| warn!("Unreadable {}: {}", path.to_str().unwrap(), err));
+    info!(
+        "Manifest verification: {} missing, {} changed, {} new, {} unreadable",
+        report.missing.len(),
+        report.changed.len(),
+        report.new.len(),
+        report.unreadable.len()
+    );
+    if args.repair_manifest {
+        write_manifest(
+            &mut BufWriter::new(File::create(manifest_path)?),
+            &report.current,
+            reference_dir,
+        )?;
+        info!("Repaired manifest {}", manifest_path.to_str().unwrap());
+    } else if !report.is_clean() {
+        return Err(DupsrmError::Verification(
+            "Manifest does not match the reference directory".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Canonicalize the root directories and check them against the reference directory
+///
+/// Paths which aren't directories are rejected with --require-dirs, nested directories unless
+/// --allow-overlap is given.
+fn check_directories(
+    args: &Cli,
+    reference_dir: Option<&Path>,
+    reference_list: Option<&[PathBuf]>,
+) -> Result<Vec<PathBuf>, DupsrmError> {
+    let mut root_dirs: Vec<PathBuf> = Vec::with_capacity(args.root_dirs.len());
+    for root_dir in &args.root_dirs {
+        let root_dir = match root_dir.canonicalize() {
+            Ok(dir) => dir,
+            Err(err) => {
+                error!("Error checking root path: {}", err);
+                return Err(err.into());
+            }
+        };
+        if root_dir.is_dir() {
+            info!("Root directory: {}", root_dir.to_str().unwrap());
+        } else if args.require_dirs {
+            error!(
+                "Root path {} is not a directory",
+                root_dir.to_str().unwrap()
+            );
+            return Err(DupsrmError::NotADirectory(root_dir));
+        } else {
+            warn!(
+                "Root path {} should be a directory",
+                root_dir.to_str().unwrap()
+            );
+        }
+        root_dirs.push(root_dir);
+    }
+    if let Some(reference_dir) = reference_dir {
+        if reference_dir.is_dir() {
+            info!("Reference directory: {}", reference_dir.to_str().unwrap());
+        } else if args.require_dirs {
+            error!(
+                "Reference path {} is not a directory",
+                reference_dir.to_str().unwrap()
+            );
+            return Err(DupsrmError::NotADirectory(reference_dir.to_path_buf()));
+        } else {
+            warn!(
+                "Reference path {} should be a directory",
+                reference_dir.to_str().unwrap()
+            );
+        }
+        check_distinct(reference_dir, &root_dirs).inspect_err(|err| error!("{}", err))?;
+        // Nested directories are excluded from the walk of the enclosing directory
+        for root_dir in &root_dirs {
+            let msg = if is_subdirectory(reference_dir, root_dir) {
+                format!(
+                    "Reference directory is nested inside root directory {}",
+                    root_dir.to_str().unwrap()
+                )
+            } else if is_subdirectory(root_dir, reference_dir) {
+                format!(
+                    "Root directory {} is nested inside reference directory",
+                    root_dir.to_str().unwrap()
+                )
+            } else {
+                continue;
+            };
+            if !args.allow_overlap {
+                error!("{}, pass --allow-overlap to proceed", msg);
+                return Err(DupsrmError::Argument(msg));
+            }
+            warn!("{}", msg);
+        }
+    }
+    if let Some(reference_list) = reference_list {
+        let source = match &args.reference_from {
+            Some(list_path) => list_path.to_str().unwrap(),
+            None => "stdin",
+        };
+        info!(
+            "Reference files: {} read from {}",
+            reference_list.len(),
+            source
+        );
+    }
+    Ok(root_dirs)
+}
+
+/// Read the modification time of the marker file, if it is readable
+fn read_marker_time(marker: Option<&Path>) -> Option<SystemTime> {
+    let marker = marker?;
+    match fs::metadata(marker).and_then(|m| m.modified()) {
+        Ok(time) => Some(time),
+        Err(err) => {
+            warn!(
+                "Marker file {} not readable, considering all files: {}",
+                marker.to_str().unwrap(),
+                err
+            );
+            None
+        }
+    }
+}
+
+/// Canonical paths dupsrm writes into, e.g. backup destinations, logs or checksum files
+fn output_paths(args: &Cli) -> Vec<PathBuf> {
+    let touched_marker = args.since.iter().filter(|_| args.touch_marker);
+    args.backup_dir
+        .iter()
+        .chain(args.write_checksums.iter())
+        .chain(args.log_removed.iter())
+        .chain(args.dot.iter())
+        .chain(args.cache.iter())
+        .chain(touched_marker)
+        .filter_map(|path| canonicalize_output(path))
+        .collect()
+}
+
+/// Walk a directory tree, aborting on the first entry failing to be read in strict mode
+fn walk_tree(
+    strict: bool,
+    dir: &Path,
+    excluded: &[PathBuf],
+    options: &WalkOptions,
+) -> io::Result<Vec<PathBuf>> {
+    match strict {
+        true => walk_files_strict(dir, excluded, options),
+        false => Ok(walk_files(dir, excluded, options)),
+    }
+}
+
+/// List the files of the reference directory tree, or of the listed reference paths
+fn walk_reference(
+    args: &Cli,
+    reference_dir: Option<&Path>,
+    reference_list: Option<&[PathBuf]>,
+    reference_excluded_dirs: &[PathBuf],
+    excluded_dirs: &[PathBuf],
+    options: &WalkOptions,
+) -> io::Result<Vec<PathBuf>> {
+    let mut reference_files: Vec<PathBuf> = Vec::new();
+    if let Some(reference_dir) = reference_dir {
+        reference_files = walk_tree(args.strict, reference_dir, reference_excluded_dirs, options)?;
+    }
+    for path in reference_list.into_iter().flatten() {
+        match args.reference_from {
+            // Paths read from a list file are taken as files, without walking
+            Some(_) => reference_files.push(path.clone()),
+            None => reference_files.extend(walk_tree(args.strict, path, excluded_dirs, options)?),
+        }
+    }
+    Ok(reference_files)
+}
+
+/// Check if a file carries the trusted hash attribute, if one is given
+///
+/// Files without the trusted attribute are skipped with a warning instead of hashed.
+fn has_trusted_attr(args: &Cli, path: &Path) -> bool {
+    match &args.trust_attr {
+        Some(name) => match xattr_hash(path, name) {
+            Ok(_) => true,
+            Err(err) => {
+                warn!("Skipping file {}: {}", path.to_str().unwrap(), err);
+                false
+            }
+        },
+        None => true,
+    }
+}
+
+/// Keep the walked reference files passing all filters
+fn filter_reference(
+    args: &Cli,
+    reference_files: Vec<PathBuf>,
+    filters: &ReferenceFilters,
+) -> Vec<PathBuf> {
+    reference_files
         .into_par_iter()
-        .filter(|path| match &regex {
-            Some(re) => re.is_match(path.path().to_str().unwrap_or("")),
+        .filter(|path| match &filters.regex {
+            Some(re) => {
+                let relative = match filters.reference_dir {
+                    Some(reference_dir) => path.strip_prefix(reference_dir).unwrap_or(path),
+                    None => path,
+                };
+                re.is_match(relative.to_str().unwrap_or(""))
+            }
             None => true,
         })
-        .collect();
+        .filter(|path| is_glob_selected(path, filters.include.as_ref(), filters.exclude.as_ref()))
+        .filter(|path| match filters.since {
+            Some(since) => fs::metadata(path)
+                .and_then(|m| m.modified())
+                .map_or(true, |modified| modified > since),
+            None => true,
+        })
+        .filter(|path| is_modified_in_range(path, filters.modified_after, filters.modified_before))
+        .filter(|path| is_size_in_range(path, args.min_size, args.max_size))
+        .filter(|path| has_trusted_attr(args, path))
+        .collect()
+}
 
-    let reference_pairs: Vec<(Vec<u8>, PathBuf)> = reference_files
+/// Load the precomputed root hashes of a checksum file, which replace the walk of the root
+/// directory trees
+fn load_root_checksums(
+    args: &Cli,
+    checksums_path: &Path,
+    root_dirs: &[PathBuf],
+    root_regex: Option<&Regex>,
+    listed_files: &HashSet<PathBuf>,
+) -> Result<Vec<(Vec<u8>, PathBuf)>, DupsrmError> {
+    let base_dir = match (root_dirs.first(), &args.base_dir) {
+        (Some(root_dir), _) => root_dir.clone(),
+        (None, Some(base_dir)) => base_dir.clone(),
+        (None, None) => PathBuf::from("."),
+    };
+    let manifest = read_manifest(checksums_path, &base_dir)?;
+    let digest_length = empty_hash(&args.hash_algorithm).len();
+    if let Some((hash, _)) = manifest.iter().find(|pair| pair.0.len() != digest_length) {
+        let msg = format!(
+            "Checksum file {} holds {}-byte digests, but {} produces {}-byte digests",
+            checksums_path.to_str().unwrap(),
+            hash.len(),
+            args.hash_algorithm,
+            digest_length
+        );
+        error!("{}", msg);
+        return Err(DupsrmError::Argument(msg));
+    }
+    // Listed files which no longer exist can't back a duplicate
+    let root_pairs: Vec<(Vec<u8>, PathBuf)> = manifest
         .into_par_iter()
-        .map(|e| {
-            (
-                hash_sum(e.path()).unwrap(),
-                fs::canonicalize(e.path()).unwrap(),
-            )
+        .filter_map(|(hash, path)| match path.canonicalize() {
+            Ok(path) => Some((hash, path)),
+            Err(err) => {
+                warn!("Skipping root file {}: {}", path.to_str().unwrap(), err);
+                None
+            }
+        })
+        .filter(|pair| !listed_files.contains(&pair.1))
+        .filter(|pair| match root_regex {
+            Some(re) => re.is_match(pair.1.to_str().unwrap_or("")),
+            None => true,
         })
-        .filter(|pair| !is_empty_hash(&pair.0, &args.hash_algorithm))
+        .filter(|pair| args.keep_empty || !is_empty_hash(&pair.0, &args.hash_algorithm))
         .collect();
+    info!(
+        "Root files: {} loaded from {}",
+        root_pairs.len(),
+        checksums_path.to_str().unwrap()
+    );
+    Ok(root_pairs)
+}
 
-    // Find duplicates
-    debug!("Check for duplicates");
-    let mut root_hashmap: HashMap<Vec<u8>, &PathBuf> = HashMap::new();
-    root_pairs.iter().for_each(|pair| {
-        root_hashmap.insert(pair.0.clone(), &pair.1);
-    });
-    let mut duplicate_pairs: Vec<(Vec<u8>, PathBuf)> = reference_pairs
+/// List the root files passing the filters, walked or read from a list file
+fn walk_root(
+    args: &Cli,
+    root_dirs: &[PathBuf],
+    root_excluded_dirs: &[PathBuf],
+    options: &WalkOptions,
+    root_regex: Option<&Regex>,
+    listed_files: &HashSet<PathBuf>,
+) -> Result<Vec<PathBuf>, DupsrmError> {
+    let mut root_files: Vec<PathBuf> = Vec::new();
+    match &args.root_from {
+        // Paths read from a list file are taken as files, without walking
+        Some(list_path) => {
+            let list = read_path_list(BufReader::new(File::open(list_path)?), true)?;
+            root_files = canonicalize_files(resolve_list(list, args.base_dir.as_deref()));
+            info!(
+                "Root files: {} read from {}",
+                root_files.len(),
+                list_path.to_str().unwrap()
+            );
+        }
+        None => {
+            for root_dir in root_dirs {
+                root_files.extend(walk_tree(
+                    args.strict,
+                    root_dir,
+                    root_excluded_dirs,
+                    options,
+                )?);
+            }
+        }
+    }
+    // Files of nested root directories are walked more than once
+    root_files.sort();
+    root_files.dedup();
+    let root_files: Vec<PathBuf> = root_files
         .into_par_iter()
-        .filter(|pair| root_hashmap.contains_key(&pair.0))
+        .filter(|path| !listed_files.contains(path))
+        .filter(|path| match root_regex {
+            Some(re) => re.is_match(path.to_str().unwrap_or("")),
+            None => true,
+        })
+        .filter(|path| is_size_in_range(path, args.min_size, args.max_size))
+        .filter(|path| has_trusted_attr(args, path))
         .collect();
-    duplicate_pairs.sort_by(|a, b| a.1.cmp(&b.1));
-    info!("{:?}", duplicate_pairs);
+    Ok(match options.follow_links {
+        true => canonicalize_files(root_files),
+        false => root_files,
+    })
+}
 
-    if duplicate_pairs.is_empty() {
-        info!("No duplicates found");
-        return Ok(());
+/// Write the reference checksums and the graph of all files sharing a hash, if asked to
+fn write_hash_outputs(
+    args: &Cli,
+    root_pairs: &[(Vec<u8>, PathBuf)],
+    reference_pairs: &[(Vec<u8>, PathBuf)],
+    reference_dir: Option<&Path>,
+) -> io::Result<()> {
+    // Paths inside the reference directory are written relative to it, like `sha256sum` does
+    if let Some(checksums_path) = &args.write_checksums {
+        write_manifest(
+            &mut BufWriter::new(File::create(checksums_path)?),
+            reference_pairs,
+            reference_dir.unwrap_or(Path::new("")),
+        )?;
+        info!(
+            "Wrote {} checksums to {}",
+            reference_pairs.len(),
+            checksums_path.to_str().unwrap()
+        );
     }
 
-    if !args.dry_run {
-        duplicate_pairs
-            .par_iter()
-            .for_each(|pair| match fs::remove_file(&pair.1) {
-                Ok(()) => info!("Removed file {}", pair.1.to_str().unwrap()),
-                Err(err) => error!("Removing file {} failed: {}", pair.1.to_str().unwrap(), err),
-            });
+    // Write the graph of all files sharing a hash
+    if let Some(dot_path) = &args.dot {
+        let groups = group_by_hash(root_pairs.iter().chain(reference_pairs.iter()));
+        write_dot(
+            &mut BufWriter::new(File::create(dot_path)?),
+            &groups,
+            args.min_group,
+        )?;
+        info!("Wrote duplicate graph to {}", dot_path.to_str().unwrap());
+    }
+    Ok(())
+}
+
+/// Build the index of the files every duplicate is matched against
+fn build_root_index<'a>(
+    args: &Cli,
+    root_pairs: &'a [(Vec<u8>, PathBuf)],
+    reference_pairs: &'a [(Vec<u8>, PathBuf)],
+) -> Index<'a> {
+    let index_state = match args.index_key {
+        Some(key) => KeyedState::new(key),
+        None => KeyedState::random(),
+    };
+    match &args.priority {
+        _ if args.within => build_within_index(reference_pairs, index_state, &args.keep),
+        Some(priority) => build_priority_index(
+            root_pairs,
+            reference_pairs,
+            index_state,
+            priority,
+            &args.prefer_root_by,
+        ),
+        None => build_index(root_pairs, index_state, &args.prefer_root_by),
+    }
+}
+
+/// Choose how files sharing a hash are verified to be duplicates
+fn choose_strategy(args: &Cli) -> Box<dyn MatchStrategy> {
+    // Collisions of non-cryptographic hashes are likely enough to always verify duplicates
+    if args.no_verify && !args.hash_algorithm.is_cryptographic() {
+        warn!(
+            "Ignoring --no-verify, {} is not a cryptographic hash",
+            args.hash_algorithm
+        );
+    }
+    if args.no_verify && args.hash_algorithm.is_cryptographic() {
+        Box::new(ExactHash)
+    } else if let Some(length) = args.verify_prefix.or(args.hash_bytes) {
+        Box::new(Prefix { length })
     } else {
-        duplicate_pairs
-            .into_par_iter()
-            .for_each(|s| info!("Found {}", s.1.to_str().unwrap()));
+        Box::new(ByteVerify)
+    }
+}
+
+/// Match the reference directories as a whole against the root directories
+fn match_reference_dirs(
+    args: &Cli,
+    reference_dir: &Path,
+    root_dirs: &[PathBuf],
+    root_pairs: &[(Vec<u8>, PathBuf)],
+    reference_pairs: &[(Vec<u8>, PathBuf)],
+    walked_files: Vec<PathBuf>,
+    strategy: &dyn MatchStrategy,
+) -> Vec<DuplicateDirectory> {
+    let hashed: HashSet<&PathBuf> = reference_pairs.iter().map(|pair| &pair.1).collect();
+    let unhashed: Vec<PathBuf> = walked_files
+        .into_iter()
+        .filter(|path| !hashed.contains(path))
+        .collect();
+    let reference_hashes = hash_directories(
+        reference_pairs,
+        &unhashed,
+        reference_dir,
+        &args.hash_algorithm,
+    );
+    let root_hashes: HashMap<PathBuf, Vec<u8>> = root_dirs
+        .iter()
+        .flat_map(|root_dir| hash_directories(root_pairs, &[], root_dir, &args.hash_algorithm))
+        .collect();
+    match_directories(&root_hashes, &reference_hashes, reference_pairs, strategy)
+}
+
+/// Keep the duplicates selected by name and the duplicates besides the kept reference copy
+fn retain_selected(
+    args: &Cli,
+    duplicate_pairs: &mut Vec<(Vec<u8>, PathBuf)>,
+    root_pairs: &[(Vec<u8>, PathBuf)],
+) {
+    if args.same_name_only {
+        let names = index_names(root_pairs);
+        duplicate_pairs.retain(|pair| has_same_name(&names, pair));
+    }
+    if args.keep_one_reference {
+        // Of the reference duplicates sharing their content, the one preferred as root file is kept
+        let kept: HashSet<PathBuf> =
+            build_index(duplicate_pairs, KeyedState::random(), &args.prefer_root_by)
+                .into_values()
+                .cloned()
+                .collect();
+        duplicate_pairs.retain(|pair| !kept.contains(&pair.1));
+    }
+}
+
+/// Report an estimate of the memory used by the index and the read buffers
+fn report_memory(index: &Index) {
+    // Approximate accounting from the known structures, not a measurement of the RSS
+    let index_bytes: usize = index
+        .iter()
+        .map(|(hash, path)| hash.len() + path.as_os_str().len())
+        .sum();
+    let threads = rayon::current_num_threads();
+    info!(
+        "Index memory: {} entries, ~{} bytes",
+        index.len(),
+        index_bytes
+    );
+    info!(
+        "Buffer memory: {} threads x {} bytes, ~{} bytes",
+        threads,
+        READ_BUFFER_SIZE,
+        threads * READ_BUFFER_SIZE
+    );
+}
+
+/// Guard against a misconfigured run wiping out the reference files
+fn check_delete_limits(
+    args: &Cli,
+    duplicate_count: usize,
+    reference_count: usize,
+) -> Result<(), DupsrmError> {
+    let exceeds_count = args.max_delete.is_some_and(|max| duplicate_count > max);
+    let exceeds_fraction = args
+        .max_delete_fraction
+        .is_some_and(|max| duplicate_count as f64 > max * reference_count as f64);
+    if !args.dry_run && (exceeds_count || exceeds_fraction) {
+        let msg = format!(
+            "Refusing to process {} of {} reference files as duplicates, exceeding --max-delete or --max-delete-fraction, inspect them with --dry-run",
+            duplicate_count,
+            reference_count
+        );
+        error!("{}", msg);
+        return Err(DupsrmError::Argument(msg));
+    }
+    Ok(())
+}
+
+/// Choose what is done with the duplicates
+fn choose_action(args: &Cli, reference_dir: Option<&Path>) -> Action {
+    if args.trash {
+        Action::Trash
+    } else if args.hardlink {
+        Action::Hardlink
+    } else if args.symlink {
+        Action::Symlink
+    } else if let Some(dir) = &args.backup_dir {
+        Action::Backup {
+            dir: dir.clone(),
+            base: reference_dir.map(Path::to_path_buf),
+        }
+    } else {
+        Action::Remove
+    }
+}
+
+/// List the duplicates and ask once whether to process them all
+fn confirm_duplicates(duplicates: &[Duplicate], action: &Action) -> io::Result<bool> {
+    for duplicate in duplicates {
+        info!(
+            "Found {} (duplicate of {})",
+            duplicate.reference.to_str().unwrap(),
+            duplicate.root_match.to_str().unwrap()
+        );
+    }
+    let question = format!(
+        "{} {} duplicates, continue?",
+        action.present_participle(),
+        duplicates.len()
+    );
+    confirm(&mut io::stdin().lock(), &mut io::stderr(), &question)
+}
+
+/// Apply the action to the duplicates, logging each processed file
+///
+/// Returns whether each duplicate was processed, and the number of duplicates skipped as their
+/// content changed since they were hashed.
+fn apply_action(
+    args: &Cli,
+    hasher: &FileHasher,
+    duplicates: &[Duplicate],
+    action: &Action,
+) -> io::Result<(Vec<bool>, usize)> {
+    // Open the log before touching any file
+    let mut removal_log = match &args.log_removed {
+        Some(path) => Some(BufWriter::new(
+            OpenOptions::new().create(true).append(true).open(path)?,
+        )),
+        None => None,
+    };
+
+    // Without --keep-going no further file is touched after the first failure
+    let failed = AtomicBool::new(false);
+    let changed_count = AtomicUsize::new(0);
+    let results: Vec<Option<Result<Option<PathBuf>, io::Error>>> = if args.batch_unlink {
+        let paths: Vec<&Path> = duplicates
+            .iter()
+            .map(|duplicate| duplicate.reference.as_path())
+            .collect();
+        remove_batched(&paths, args.keep_going)
+            .into_iter()
+            .zip(&paths)
+            .map(|(result, path)| {
+                if result.is_none() {
+                    warn!(
+                        "Skipped file {} after a previous failure",
+                        path.to_str().unwrap()
+                    );
+                }
+                result.map(|result| result.map(|_| None))
+            })
+            .collect()
+    } else {
+        duplicates
+            .par_iter()
+            .map(|duplicate| {
+                let path = &duplicate.reference;
+                if !args.keep_going && failed.load(Ordering::Relaxed) {
+                    warn!(
+                        "Skipped file {} after a previous failure",
+                        path.to_str().unwrap()
+                    );
+                    return None;
+                }
+                // Hash the content itself, a cached hash may no longer describe the file
+                if args.recheck_before_delete {
+                    match is_unchanged(path, &duplicate.hash, |path| hasher.content_hash(path)) {
+                        Ok(true) => {}
+                        Ok(false) => {
+                            changed_count.fetch_add(1, Ordering::Relaxed);
+                            warn!(
+                                "Skipped file {}, its content changed since it was hashed",
+                                path.to_str().unwrap()
+                            );
+                            return None;
+                        }
+                        Err(err) => {
+                            warn!(
+                                "Skipped file {}, rehashing failed: {}",
+                                path.to_str().unwrap(),
+                                err
+                            );
+                            return None;
+                        }
+                    }
+                }
+                let result = action.apply(path, &duplicate.root_match);
+                if result.is_err() {
+                    failed.store(true, Ordering::Relaxed);
+                }
+                Some(result)
+            })
+            .collect()
+    };
+    let removed = duplicates
+        .iter()
+        .zip(results)
+        .map(|(duplicate, result)| match result {
+            Some(Ok(destination)) => {
+                info!(
+                    "{} file {}",
+                    action.past_tense(),
+                    duplicate.reference.to_str().unwrap()
+                );
+                if let Some(log) = &mut removal_log {
+                    let entry = RemovalLogEntry {
+                        reference_path: duplicate.reference.clone(),
+                        matching_root_path: duplicate.root_match.clone(),
+                        hash: to_hex(&duplicate.hash),
+                        size: duplicate.size,
+                        action: action.past_tense().to_lowercase(),
+                        destination,
+                        timestamp: SystemTime::now()
+                            .duration_since(SystemTime::UNIX_EPOCH)
+                            .map_or(0, |duration| duration.as_secs()),
+                    };
+                    if let Err(err) = write_log_entry(log, &entry) {
+                        error!("Writing the removal log failed: {}", err);
+                    }
+                }
+                true
+            }
+            Some(Err(err)) => {
+                error!(
+                    "{} file {} failed: {}",
+                    action.present_participle(),
+                    duplicate.reference.to_str().unwrap(),
+                    err
+                );
+                false
+            }
+            // Skipped with a warning already
+            None => false,
+        })
+        .collect();
+    if let Some(log) = &mut removal_log {
+        log.flush()?;
+    }
+    Ok((removed, changed_count.into_inner()))
+}
+
+/// Log the found duplicates of a dry run and write their paths to stdout
+fn list_duplicates(
+    args: &Cli,
+    duplicate_dirs: &[DuplicateDirectory],
+    duplicates: &[Duplicate],
+) -> io::Result<()> {
+    // Logged serially in path order, so the output of runs can be compared
+    for dir in duplicate_dirs {
+        info!(
+            "Found directory {} (duplicate of {})",
+            dir.reference.to_str().unwrap(),
+            dir.root_match.to_str().unwrap()
+        );
+    }
+    for duplicate in duplicates {
+        info!(
+            "Found {} (duplicate of {})",
+            duplicate.reference.to_str().unwrap(),
+            duplicate.root_match.to_str().unwrap()
+        );
+    }
+    // The found paths are the result, written to stdout unless another output takes it
+    let other_output =
+        args.format != OutputFormat::Text || args.exit_code || args.count_only || args.json_summary;
+    if args.print0 || !other_output {
+        write_path_list(
+            &mut io::stdout().lock(),
+            duplicates
+                .iter()
+                .map(|duplicate| duplicate.reference.as_path()),
+            args.print0,
+        )?;
+    }
+    Ok(())
+}
+
+/// Remove the duplicate directories whose files are all gone
+fn remove_duplicate_dirs(
+    duplicate_dirs: &[DuplicateDirectory],
+    duplicates: &[Duplicate],
+    removed: &[bool],
+) {
+    let removed_paths: HashSet<&PathBuf> = duplicates
+        .iter()
+        .zip(removed.iter())
+        .filter(|(_, removed)| **removed)
+        .map(|(duplicate, _)| &duplicate.reference)
+        .collect();
+    for dir in duplicate_dirs {
+        let emptied = dir
+            .files
+            .iter()
+            .all(|file| removed_paths.contains(&file.reference));
+        if !emptied {
+            warn!(
+                "Kept directory {}, as some of its files are left",
+                dir.reference.to_str().unwrap()
+            );
+            continue;
+        }
+        match remove_empty_dirs(&dir.reference) {
+            Ok(()) => info!(
+                "Removed directory {} (duplicate of {})",
+                dir.reference.to_str().unwrap(),
+                dir.root_match.to_str().unwrap()
+            ),
+            Err(err) => warn!(
+                "Kept directory {}: {}",
+                dir.reference.to_str().unwrap(),
+                err
+            ),
+        }
     }
+}
+
+/// Log the totals of the run and write the requested reports to stdout
+fn report_results(
+    args: &Cli,
+    counts: &RunCounts,
+    duplicates: &[Duplicate],
+    removed: &[bool],
+    action: &Action,
+) -> io::Result<()> {
+    let removed_count = removed.iter().filter(|removed| **removed).count();
+    let freed: u64 = duplicates
+        .iter()
+        .zip(removed.iter())
+        .filter(|(_, removed)| **removed)
+        .map(|(duplicate, _)| duplicate.size)
+        .sum();
+    if !duplicates.is_empty() {
+        if args.dry_run {
+            info!(
+                "Found {} duplicates, would free {}",
+                duplicates.len(),
+                format_bytes(duplicates.iter().map(|duplicate| duplicate.size).sum())
+            );
+        } else {
+            info!(
+                "{} {} files, freed {}",
+                action.past_tense(),
+                removed_count,
+                format_bytes(freed)
+            );
+        }
+    }
+
+    // Tell where files dropped out, e.g. if the filters left nothing to match
+    info!(
+        "Summary: {} reference files walked, {} passed the filters, {} hashed, {} root files hashed, {} duplicates found, {} {}",
+        counts.walked,
+        counts.filtered,
+        counts.hashed,
+        counts.root_hashed,
+        duplicates.len(),
+        removed_count,
+        action.past_tense().to_lowercase()
+    );
 
+    if args.format != OutputFormat::Text {
+        let records: Vec<DuplicateRecord> = duplicates
+            .iter()
+            .zip(removed.iter())
+            .map(|(duplicate, removed)| DuplicateRecord {
+                reference_path: duplicate.reference.clone(),
+                matching_root_path: duplicate.root_match.clone(),
+                hash: to_hex(&duplicate.hash),
+                size: duplicate.size,
+                removed: *removed,
+            })
+            .collect();
+        match args.format {
+            OutputFormat::Csv => write_csv(&mut io::stdout().lock(), &records)?,
+            _ => write_json(&mut io::stdout().lock(), &records)?,
+        }
+    }
+
+    if args.count_only {
+        writeln!(io::stdout().lock(), "{}", duplicates.len())?;
+    }
+
+    if args.json_summary {
+        let summary = RunSummary {
+            scanned: counts.walked,
+            hashed: counts.hashed,
+            duplicates_found: duplicates.len(),
+            removed: removed_count,
+            bytes_freed: freed,
+            algorithm: args.hash_algorithm.to_string(),
+            dry_run: args.dry_run,
+        };
+        write_json_summary(&mut io::stdout().lock(), &summary)?;
+    }
     Ok(())
 }
+
+/// Log the duration of each phase and the hashing throughput, if asked to
+fn report_timings(args: &Cli, timings: &Timings, hasher: &FileHasher) {
+    if args.timings {
+        for (phase, duration) in timings.phases() {
+            info!("Timing {}: {:?}", phase, duration);
+        }
+    }
+    if args.verbose {
+        // The streamed reference hashing phase includes matching, so this is a lower bound
+        let hashing: Duration = timings
+            .phases()
+            .iter()
+            .filter(|(phase, _)| phase.starts_with("hash-"))
+            .map(|(_, duration)| *duration)
+            .sum();
+        let bytes = hasher.hashed_bytes.load(Ordering::Relaxed);
+        info!(
+            "Hash throughput: {} in {:?}, {:.1} MB/s with {}",
+            format_bytes(bytes),
+            hashing,
+            bytes as f64 / 1e6 / hashing.as_secs_f64().max(f64::EPSILON),
+            args.hash_algorithm
+        );
+    }
+}
@@ -0,0 +1,130 @@
+use super::finder::{walk_files, WalkOptions};
+use super::hasher::{from_hex, to_hex};
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// Read a manifest in the coreutils checksum format `<hex>  <path>`
+///
+/// Relative paths are resolved against the base directory.
+pub fn read_manifest(path: &Path, base_dir: &Path) -> Result<Vec<(Vec<u8>, PathBuf)>, io::Error> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut pairs: Vec<(Vec<u8>, PathBuf)> = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let (hex, file_path) = match line.split_once(' ') {
+            // The second separator character is ' ' in text and '*' in binary mode
+            Some((hex, rest)) if rest.starts_with(' ') || rest.starts_with('*') => {
+                (hex, &rest[1..])
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Malformed manifest line: {}", line),
+                ))
+            }
+        };
+        pairs.push((from_hex(hex)?, base_dir.join(file_path)));
+    }
+    Ok(pairs)
+}
+
+/// Write a manifest in the coreutils checksum format `<hex>  <path>`
+///
+/// Paths inside the base directory are written relative to it.
+pub fn write_manifest<W: Write>(
+    writer: &mut W,
+    pairs: &[(Vec<u8>, PathBuf)],
+    base_dir: &Path,
+) -> Result<(), io::Error> {
+    for (hash, path) in pairs {
+        let path = path.strip_prefix(base_dir).unwrap_or(path);
+        writeln!(writer, "{}  {}", to_hex(hash), path.to_string_lossy())?;
+    }
+    writer.flush()
+}
+
+/// Discrepancies between a manifest and the files of a directory
+#[derive(Default)]
+pub struct ManifestReport {
+    /// Files listed in the manifest but not found in the directory
+    pub missing: Vec<PathBuf>,
+    /// Files whose hash differs from the manifest
+    pub changed: Vec<PathBuf>,
+    /// Files found in the directory but not listed in the manifest
+    pub new: Vec<PathBuf>,
+    /// Files found in the directory but failing to hash, with the error
+    pub unreadable: Vec<(PathBuf, io::Error)>,
+    /// Current hashes of all files in the directory
+    pub current: Vec<(Vec<u8>, PathBuf)>,
+}
+
+impl ManifestReport {
+    /// Check if the manifest matches the directory
+    pub fn is_clean(&self) -> bool {
+        self.missing.is_empty()
+            && self.changed.is_empty()
+            && self.new.is_empty()
+            && self.unreadable.is_empty()
+    }
+}
+
+/// Rehash all files of a directory and compare them with the manifest entries
+///
+/// Files inside the excluded paths, e.g. the manifest itself, are skipped.
+pub fn verify_manifest<F>(
+    manifest: &[(Vec<u8>, PathBuf)],
+    dir: &Path,
+    excluded: &[PathBuf],
+    hash_sum: F,
+) -> ManifestReport
+where
+    F: Fn(&Path) -> Result<Vec<u8>, io::Error> + Sync,
+{
+    let files: Vec<PathBuf> = walk_files(dir, excluded, &WalkOptions::default());
+    let mut hashed: Vec<(PathBuf, Result<Vec<u8>, io::Error>)> = files
+        .into_par_iter()
+        .map(|path| {
+            let hash = hash_sum(&path);
+            (path, hash)
+        })
+        .collect();
+    hashed.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut report = ManifestReport::default();
+    let mut current: Vec<(Vec<u8>, PathBuf)> = Vec::with_capacity(hashed.len());
+    for (path, hash) in hashed {
+        match hash {
+            Ok(hash) => current.push((hash, path)),
+            Err(err) => report.unreadable.push((path, err)),
+        }
+    }
+    let expected: HashMap<&PathBuf, &Vec<u8>> =
+        manifest.iter().map(|pair| (&pair.1, &pair.0)).collect();
+    for (hash, path) in &current {
+        match expected.get(path) {
+            Some(expected_hash) if *expected_hash != hash => report.changed.push(path.clone()),
+            Some(_) => (),
+            None => report.new.push(path.clone()),
+        }
+    }
+    // Unreadable files exist, so they aren't missing
+    let found: HashSet<&PathBuf> = current
+        .iter()
+        .map(|pair| &pair.1)
+        .chain(report.unreadable.iter().map(|(path, _)| path))
+        .collect();
+    report.missing = manifest
+        .iter()
+        .filter(|pair| !found.contains(&pair.1))
+        .map(|pair| pair.1.clone())
+        .collect();
+    report.missing.sort();
+    report.current = current;
+    report
+}
@@ -1,15 +1,179 @@
-use std::path::Path;
+use super::error::DupsrmError;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use regex::{Regex, RegexBuilder};
+use std::ffi::OsString;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
 use walkdir::DirEntry;
 
 /// Check if the path is a subdirectory of the reference path
+///
+/// The paths are compared component-wise, so `/data/reference_backup` is not considered a
+/// subdirectory of `/data/ref`. A path is a subdirectory of itself.
 pub fn is_subdirectory(entry: &Path, reference: &Path) -> bool {
-    entry
-        .to_str()
-        .unwrap()
-        .starts_with(reference.to_str().unwrap())
+    let mut entry_components = entry.components();
+    reference
+        .components()
+        .all(|component| entry_components.next() == Some(component))
+}
+
+/// Check if the path is located inside any of the excluded directories
+pub fn is_excluded(entry: &Path, excluded: &[PathBuf]) -> bool {
+    excluded.iter().any(|dir| is_subdirectory(entry, dir))
+}
+
+/// Check if the final component of the path is one of the excluded directory names
+pub fn has_excluded_name(path: &Path, names: &[OsString]) -> bool {
+    path.file_name()
+        .is_some_and(|name| names.iter().any(|excluded| excluded == name))
+}
+
+/// Check if the final component of the path starts with a dot
+pub fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+}
+
+/// Resolve a relative path against a base directory, absolute paths are kept
+pub fn resolve(path: &Path, base_dir: &Path) -> PathBuf {
+    if path.is_relative() {
+        base_dir.join(path)
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// Canonicalize a path which may not exist yet, e.g. an output file, through its parent directory
+pub fn canonicalize_output(path: &Path) -> Option<PathBuf> {
+    path.canonicalize().ok().or_else(|| {
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        Some(parent.canonicalize().ok()?.join(path.file_name()?))
+    })
+}
+
+/// Check if two paths refer to the same file, e.g. through a symbolic link
+pub fn is_same_file(a: &Path, b: &Path) -> bool {
+    a == b
+        || a.canonicalize()
+            .is_ok_and(|a| b.canonicalize().is_ok_and(|b| a == b))
+}
+
+/// Build a set of glob patterns, or none if there is no pattern
+pub fn build_glob_set(patterns: &[String]) -> Result<Option<GlobSet>, globset::Error> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern)?);
+    }
+    Ok(Some(builder.build()?))
+}
+
+/// Compile a regular expression filtering paths, optionally matching case-insensitively
+///
+/// Syntax errors span several lines pointing at the failing position, so only the reason in
+/// their last line is kept.
+pub fn build_regex(pattern: &str, ignore_case: bool) -> Result<Regex, DupsrmError> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(ignore_case)
+        .build()
+        .map_err(|err| {
+            let msg = err.to_string();
+            let reason = msg.lines().last().unwrap_or_default();
+            DupsrmError::InvalidRegex(reason.trim_start_matches("error: ").to_string())
+        })
+}
+
+/// Check that the reference directory is none of the root directories
+pub fn check_distinct(reference_dir: &Path, root_dirs: &[PathBuf]) -> Result<(), DupsrmError> {
+    match root_dirs.iter().any(|root_dir| root_dir == reference_dir) {
+        true => Err(DupsrmError::SameDirectory),
+        false => Ok(()),
+    }
+}
+
+/// Check if a path matches any include pattern, if there are any, and no exclude pattern
+pub fn is_glob_selected(path: &Path, include: Option<&GlobSet>, exclude: Option<&GlobSet>) -> bool {
+    include.is_none_or(|include| include.is_match(path))
+        && exclude.is_none_or(|exclude| !exclude.is_match(path))
 }
 
 /// Check if directory entry is a file
+///
+/// A symbolic link is only a file if the walk follows links and the link resolves to a
+/// regular file. Dangling symbolic links are never files.
 pub fn is_file(entry: &DirEntry) -> bool {
     entry.file_type().is_file()
 }
+
+/// Check if directory entry is a regular file and not a symbolic link, even when followed
+pub fn is_regular_file(entry: &DirEntry) -> bool {
+    !entry.path_is_symlink() && entry.file_type().is_file()
+}
+
+/// Check if the path is a symbolic link whose target doesn't exist
+pub fn is_dangling_symlink(path: &Path) -> bool {
+    path.is_symlink() && !path.exists()
+}
+
+/// Read a list of paths separated by newlines, or by NUL characters with `null`
+///
+/// Empty entries are skipped.
+pub fn read_path_list<R: BufRead>(reader: R, null: bool) -> Result<Vec<PathBuf>, io::Error> {
+    let separator = if null { b'\0' } else { b'\n' };
+    let mut paths = Vec::new();
+    for entry in reader.split(separator) {
+        let mut entry = entry?;
+        if !null && entry.last() == Some(&b'\r') {
+            entry.pop();
+        }
+        if !entry.is_empty() {
+            paths.push(path_from_bytes(entry));
+        }
+    }
+    Ok(paths)
+}
+
+/// Write a list of paths, each terminated by a newline, or by a NUL character with `null`
+pub fn write_path_list<'a, W, I>(writer: &mut W, paths: I, null: bool) -> Result<(), io::Error>
+where
+    W: Write,
+    I: IntoIterator<Item = &'a Path>,
+{
+    let separator = if null { b'\0' } else { b'\n' };
+    for path in paths {
+        writer.write_all(&path_to_bytes(path))?;
+        writer.write_all(&[separator])?;
+    }
+    writer.flush()
+}
+
+/// Convert a path into raw bytes
+#[cfg(unix)]
+fn path_to_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+/// Convert a path into raw bytes
+#[cfg(not(unix))]
+fn path_to_bytes(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().into_owned().into_bytes()
+}
+
+/// Convert raw bytes into a path
+#[cfg(unix)]
+fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+    PathBuf::from(OsString::from_vec(bytes))
+}
+
+/// Convert raw bytes into a path
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
@@ -0,0 +1,21 @@
+/// Check if the process runs with the effective user id of root
+#[cfg(unix)]
+pub fn is_elevated() -> bool {
+    // SAFETY: geteuid has no preconditions and cannot fail
+    unsafe { libc::geteuid() == 0 }
+}
+
+/// Check if the process runs elevated
+///
+/// Elevation is only detected on Unix platforms.
+#[cfg(not(unix))]
+pub fn is_elevated() -> bool {
+    false
+}
+
+/// Check if files may be removed given the privileges of the process
+///
+/// An elevated process only removes files when explicitly allowed, a dry-run is always allowed.
+pub fn may_remove(elevated: bool, allow_root: bool, dry_run: bool) -> bool {
+    !elevated || allow_root || dry_run
+}
@@ -0,0 +1,33 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use std::io::{self, IsTerminal};
+use std::path::Path;
+
+/// Create a progress bar counting the files of a phase
+///
+/// The bar is hidden if disabled or if stdout isn't a terminal, so piped output stays clean.
+pub fn progress_bar(len: usize, phase: &'static str, enabled: bool) -> ProgressBar {
+    if !enabled || !io::stdout().is_terminal() {
+        return ProgressBar::hidden();
+    }
+    let style = ProgressStyle::with_template("{msg} [{bar:40}] {pos}/{len} files, ETA {eta}")
+        .unwrap()
+        .progress_chars("=> ");
+    ProgressBar::new(len as u64)
+        .with_style(style)
+        .with_message(phase)
+}
+
+/// Wrap a function on files so each call advances the progress bar
+pub fn advancing<'a, F, T>(
+    function: F,
+    progress: &'a ProgressBar,
+) -> impl Fn(&Path) -> T + Sync + 'a
+where
+    F: Fn(&Path) -> T + Sync + 'a,
+{
+    move |path: &Path| {
+        let result = function(path);
+        progress.inc(1);
+        result
+    }
+}
@@ -0,0 +1,193 @@
+use super::hasher::to_hex;
+use byte_unit::{Byte, UnitType};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{self, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// Group file paths by their hash in a deterministic order
+pub fn group_by_hash<'a, I>(pairs: I) -> BTreeMap<Vec<u8>, Vec<PathBuf>>
+where
+    I: Iterator<Item = &'a (Vec<u8>, PathBuf)>,
+{
+    let mut groups: BTreeMap<Vec<u8>, Vec<PathBuf>> = BTreeMap::new();
+    pairs.for_each(|pair| {
+        groups
+            .entry(pair.0.clone())
+            .or_default()
+            .push(pair.1.clone())
+    });
+    groups.values_mut().for_each(|paths| paths.sort());
+    groups
+}
+
+/// Format a number of bytes with binary unit prefixes, e.g. `1.3 GiB`
+pub fn format_bytes(bytes: u64) -> String {
+    // Whole bytes are written without a fraction
+    format!(
+        "{:.1}",
+        Byte::from_u64(bytes).get_appropriate_unit(UnitType::Binary)
+    )
+}
+
+/// Quote a path as a GraphViz DOT identifier
+fn dot_id(path: &Path) -> String {
+    format!(
+        "\"{}\"",
+        path.to_string_lossy()
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+    )
+}
+
+/// Write a GraphViz DOT graph with one cluster per group of files sharing a hash
+///
+/// Groups with fewer than `min_group` files are omitted. Within a cluster every file is
+/// connected to the first file of the group.
+pub fn write_dot<W: Write>(
+    writer: &mut W,
+    groups: &BTreeMap<Vec<u8>, Vec<PathBuf>>,
+    min_group: usize,
+) -> io::Result<()> {
+    writeln!(writer, "graph duplicates {{")?;
+    for (index, (hash, paths)) in groups
+        .iter()
+        .filter(|(_, paths)| paths.len() >= min_group)
+        .enumerate()
+    {
+        writeln!(writer, "    subgraph cluster_{} {{", index)?;
+        writeln!(writer, "        label=\"{}\";", to_hex(hash))?;
+        for path in paths {
+            writeln!(writer, "        {};", dot_id(path))?;
+        }
+        for path in paths.iter().skip(1) {
+            writeln!(writer, "        {} -- {};", dot_id(&paths[0]), dot_id(path))?;
+        }
+        writeln!(writer, "    }}")?;
+    }
+    writeln!(writer, "}}")?;
+    writer.flush()
+}
+
+/// Report entry of a duplicated reference file
+#[derive(Serialize, Deserialize)]
+pub struct DuplicateRecord {
+    pub reference_path: PathBuf,
+    pub matching_root_path: PathBuf,
+    pub hash: String,
+    pub size: u64,
+    pub removed: bool,
+}
+
+/// Log entry of a processed duplicate, written as one JSON line
+#[derive(Serialize, Deserialize)]
+pub struct RemovalLogEntry {
+    pub reference_path: PathBuf,
+    pub matching_root_path: PathBuf,
+    pub hash: String,
+    pub size: u64,
+    /// Action applied to the file, e.g. `removed` or `backed up`
+    pub action: String,
+    /// New path of the file, if it was moved to a known location
+    pub destination: Option<PathBuf>,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+}
+
+/// Append a log entry as a single JSON line
+pub fn write_log_entry<W: Write>(writer: &mut W, entry: &RemovalLogEntry) -> io::Result<()> {
+    serde_json::to_writer(&mut *writer, entry)?;
+    writeln!(writer)
+}
+
+/// Totals of a run, written as a single JSON object for scripts
+#[derive(Serialize, Deserialize)]
+pub struct RunSummary {
+    /// Number of walked reference files
+    pub scanned: usize,
+    /// Number of hashed reference files
+    pub hashed: usize,
+    pub duplicates_found: usize,
+    /// Number of processed duplicates, 0 in a dry run
+    pub removed: usize,
+    /// Total size of the processed duplicates
+    pub bytes_freed: u64,
+    pub algorithm: String,
+    pub dry_run: bool,
+}
+
+/// Write the summary as a single JSON line
+pub fn write_json_summary<W: Write>(writer: &mut W, summary: &RunSummary) -> io::Result<()> {
+    serde_json::to_writer(&mut *writer, summary)?;
+    writeln!(writer)?;
+    writer.flush()
+}
+
+/// Write the duplicates as a JSON array
+pub fn write_json<W: Write>(writer: &mut W, records: &[DuplicateRecord]) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut *writer, records)?;
+    writeln!(writer)?;
+    writer.flush()
+}
+
+/// Write the duplicates as CSV with a header row, quoting paths as needed
+pub fn write_csv<W: Write>(writer: &mut W, records: &[DuplicateRecord]) -> io::Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record([
+        "reference_path",
+        "root_path",
+        "hash_hex",
+        "size_bytes",
+        "removed",
+    ])?;
+    for record in records {
+        writer.write_record([
+            record.reference_path.to_string_lossy().into_owned(),
+            record.matching_root_path.to_string_lossy().into_owned(),
+            record.hash.clone(),
+            record.size.to_string(),
+            record.removed.to_string(),
+        ])?;
+    }
+    writer.flush()
+}
+
+/// Read a JSON report written by a previous run
+pub fn read_json(path: &Path) -> io::Result<Vec<DuplicateRecord>> {
+    Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+}
+
+/// Summary of several reports
+pub struct MergedReport {
+    /// Number of merged reports
+    pub reports: usize,
+    /// Number of removed files
+    pub removed: usize,
+    /// Total size of the removed files
+    pub bytes_freed: u64,
+    /// Hash found most often across the reports and its number of occurrences
+    pub most_duplicated: Option<(String, usize)>,
+}
+
+/// Merge the entries of several reports into a summary
+pub fn merge_reports(reports: &[Vec<DuplicateRecord>]) -> MergedReport {
+    let records = reports.iter().flatten();
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    records
+        .clone()
+        .for_each(|record| *counts.entry(&record.hash).or_default() += 1);
+    MergedReport {
+        reports: reports.len(),
+        removed: records.clone().filter(|record| record.removed).count(),
+        bytes_freed: records
+            .filter(|record| record.removed)
+            .map(|record| record.size)
+            .sum(),
+        // Ties are resolved by the smaller hash for a deterministic result
+        most_duplicated: counts
+            .into_iter()
+            .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(a.0)))
+            .map(|(hash, count)| (hash.to_string(), count)),
+    }
+}
@@ -0,0 +1,69 @@
+use super::compare::{is_identical, is_prefix_identical};
+use log::warn;
+use std::io;
+use std::path::Path;
+
+/// File considered for matching, with its hash value
+pub struct FileInfo<'a> {
+    pub path: &'a Path,
+    pub hash: &'a [u8],
+}
+
+/// Decides whether a reference file duplicates a root file
+pub trait MatchStrategy: Sync {
+    /// Check if the reference file is a duplicate of the candidate root file
+    fn is_duplicate(&self, reference: &FileInfo, candidate_root: &FileInfo) -> io::Result<bool>;
+}
+
+/// Trust equal hashes
+pub struct ExactHash;
+
+impl MatchStrategy for ExactHash {
+    fn is_duplicate(&self, reference: &FileInfo, candidate_root: &FileInfo) -> io::Result<bool> {
+        Ok(reference.hash == candidate_root.hash)
+    }
+}
+
+/// Compare files with equal hashes byte by byte to rule out hash collisions
+pub struct ByteVerify;
+
+impl MatchStrategy for ByteVerify {
+    fn is_duplicate(&self, reference: &FileInfo, candidate_root: &FileInfo) -> io::Result<bool> {
+        if reference.hash != candidate_root.hash {
+            return Ok(false);
+        }
+        let identical = is_identical(reference.path, candidate_root.path)?;
+        if !identical {
+            warn!(
+                "Hash collision, {} differs from {}",
+                reference.path.to_str().unwrap(),
+                candidate_root.path.to_str().unwrap()
+            );
+        }
+        Ok(identical)
+    }
+}
+
+/// Compare the sizes and the first bytes of files with equal hashes
+///
+/// A cheaper spot check than [`ByteVerify`] for large files.
+pub struct Prefix {
+    pub length: u64,
+}
+
+impl MatchStrategy for Prefix {
+    fn is_duplicate(&self, reference: &FileInfo, candidate_root: &FileInfo) -> io::Result<bool> {
+        if reference.hash != candidate_root.hash {
+            return Ok(false);
+        }
+        let identical = is_prefix_identical(reference.path, candidate_root.path, self.length)?;
+        if !identical {
+            warn!(
+                "Hash collision, {} differs from {}",
+                reference.path.to_str().unwrap(),
+                candidate_root.path.to_str().unwrap()
+            );
+        }
+        Ok(identical)
+    }
+}
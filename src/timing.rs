@@ -0,0 +1,64 @@
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Accumulates wall-clock durations of the consecutive phases of a run
+pub struct Timings {
+    phases: Vec<(&'static str, Duration)>,
+    last: Instant,
+}
+
+impl Timings {
+    pub fn new() -> Timings {
+        Timings {
+            phases: Vec::new(),
+            last: Instant::now(),
+        }
+    }
+
+    /// Add the time elapsed since the previous lap to the phase
+    pub fn lap(&mut self, phase: &'static str) {
+        let elapsed = self.last.elapsed();
+        self.last = Instant::now();
+        match self.phases.iter_mut().find(|(name, _)| *name == phase) {
+            Some((_, duration)) => *duration += elapsed,
+            None => self.phases.push((phase, elapsed)),
+        }
+    }
+
+    /// Restart the measurement without accounting the elapsed time to any phase
+    pub fn skip(&mut self) {
+        self.last = Instant::now();
+    }
+
+    /// Durations of the phases in the order they were first measured
+    pub fn phases(&self) -> &[(&'static str, Duration)] {
+        &self.phases
+    }
+}
+
+impl Default for Timings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Wrap a function on files so each call adds the size of the file to the counter, if any
+///
+/// Without a counter the size isn't read, so the wrapper costs nothing measurable.
+pub fn counting_bytes<'a, F, T>(
+    function: F,
+    counter: Option<&'a AtomicU64>,
+) -> impl Fn(&Path) -> T + Sync + 'a
+where
+    F: Fn(&Path) -> T + Sync + 'a,
+{
+    move |path: &Path| {
+        if let Some(counter) = counter {
+            let size = fs::metadata(path).map_or(0, |m| m.len());
+            counter.fetch_add(size, Ordering::Relaxed);
+        }
+        function(path)
+    }
+}
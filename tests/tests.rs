@@ -2,19 +2,42 @@
 mod tests {
 
     // use super::sha256sum
+    use dupsrm::action::remove_batched;
+    use dupsrm::compare::is_identical;
+    use dupsrm::error::DupsrmError;
+    use dupsrm::finder::{
+        build_index, canonicalize_files, device_id, find_duplicates, for_each_duplicate,
+        hash_files, hash_match_duplicates, is_on_device, is_unchanged, match_duplicates,
+        plan_candidates, prefilter_files, walk_files, walk_files_strict, Duplicate, RootPreference,
+        WalkOptions,
+    };
     use dupsrm::hasher::{
-        blake256_sum, is_empty_hash, md5sum, ripemd160_sum, sha1sum, sha256sum, sha3_256sum,
-        whirlpool_sum, HashAlgorithm,
+        blake256_sum, blake2b512_sum, blake2s256_sum, crc32_sum, empty_hash, from_hex, hash_bytes,
+        hash_file, hash_file_mapped, hash_reader, is_empty_hash, md5sum, retrying, ripemd160_sum,
+        sha1sum, sha256sum, sha384sum, sha3_256sum, sha512sum, to_hex, whirlpool_sum, xxh3_sum,
+        HashAlgorithm,
+    };
+    use dupsrm::index::KeyedState;
+    use dupsrm::path::{
+        build_regex, check_distinct, is_dangling_symlink, is_file, is_regular_file,
+        is_subdirectory, write_path_list,
     };
+    use dupsrm::privilege::may_remove;
+    use dupsrm::report::{format_bytes, RemovalLogEntry};
+    use dupsrm::strategy::{ByteVerify, ExactHash, FileInfo, MatchStrategy, Prefix};
     use serial_test::serial;
 
     use assert_cmd::prelude::*; // Add methods on commands
     use predicates::prelude::*;
+    use regex::Regex;
     use rstest::rstest;
+    use std::collections::HashSet;
     use std::fs;
-    use std::process::Command; // Used for writing assertions
+    use std::process::{Command, Stdio}; // Used for writing assertions
+    use std::time::{Duration, SystemTime};
     use std::{
-        io::Write,
+        cell::Cell,
+        io::{Read, Write},
         path::{Path, PathBuf},
     };
 
@@ -44,10 +67,10 @@ mod tests {
             let file_path_2: PathBuf = reference_dir_path.join(format!("file_test_{}.txt", k));
 
             CliTestCase {
-                root_dir_path: root_dir_path,
-                reference_dir_path: reference_dir_path,
-                file_path_1: file_path_1,
-                file_path_2: file_path_2,
+                root_dir_path,
+                reference_dir_path,
+                file_path_1,
+                file_path_2,
             }
         }
 
@@ -142,7 +165,10 @@ mod tests {
         cmd.arg(&test_case.reference_dir_path)
             .arg(&test_case.root_dir_path)
             .arg("-n");
-        cmd.assert().success();
+        cmd.assert()
+            .success()
+            .stderr(predicate::str::contains("file_test_6.txt (duplicate of "))
+            .stderr(predicate::str::contains("dir_5/file_2.txt)"));
 
         // Check results
         assert!(test_case.file_path_1.exists());
@@ -151,69 +177,211 @@ mod tests {
         test_case.teardown();
     }
 
-    #[test]
+    #[rstest]
+    #[case::found(&[], 1)]
     #[serial]
-    fn duplicates_removed() {
+    #[case::none(&["-r", "9.txt$"], 0)]
+    #[serial]
+    #[case::error(&["--root-checksums", "./test/missing.sha256"], 2)]
+    #[serial]
+    fn dry_run_exit_code(#[case] args: &[&str], #[case] code: i32) {
         let test_case = CliTestCase::new();
         test_case.startup();
 
-        // Check prerequisites
-        assert!(test_case.file_path_1.exists());
-        assert!(test_case.file_path_2.exists());
-        assert!(test_case.root_dir_path.exists());
-        assert!(test_case.reference_dir_path.exists());
-
         // Execute program
         let mut cmd = match Command::cargo_bin("dupsrm") {
             Err(err) => panic!("{}", err),
             Ok(cmd) => cmd,
         };
         cmd.arg(&test_case.reference_dir_path)
-            .arg(&test_case.root_dir_path);
-        cmd.assert().success();
+            .arg(&test_case.root_dir_path)
+            .arg("-n")
+            .arg("--exit-code")
+            .args(args);
+        cmd.assert()
+            .code(code)
+            .stdout(predicate::str::is_empty())
+            .stderr(predicate::str::contains("INFO").not());
 
         // Check results
-        assert!(!test_case.file_path_1.exists());
-        assert!(test_case.file_path_2.exists());
+        assert!(test_case.file_path_1.exists());
 
         test_case.teardown();
     }
 
-    #[test]
-    fn file_doesnt_exist() -> Result<(), Box<dyn std::error::Error>> {
-        let mut cmd = Command::cargo_bin("dupsrm")?;
+    #[rstest]
+    #[case::default(&[], "Hash algorithm: SHA2-256\n")]
+    #[serial]
+    #[case::auto(&["--algorithm", "auto"], "Hash algorithm: XXH3 (auto)")]
+    #[serial]
+    #[case::auto_verify_prefix(&["-a", "auto", "--verify-prefix", "1K"], "Hash algorithm: XXH3 (auto)")]
+    #[serial]
+    #[case::auto_no_verify(&["-a", "auto", "--no-verify"], "Hash algorithm: SHA2-256 (auto)")]
+    #[serial]
+    fn algorithm_auto(#[case] args: &[&str], #[case] resolved: &str) {
+        let test_case = CliTestCase::new();
+        test_case.startup();
 
-        cmd.arg("./dsdfgdf").arg(".");
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("-n")
+            .args(args);
         cmd.assert()
-            .failure()
-            .stderr(predicate::str::contains("No such file or directory"));
+            .success()
+            .stderr(predicate::str::contains(resolved))
+            .stderr(predicate::str::contains("file_test_6.txt (duplicate of "));
 
-        Ok(())
+        test_case.teardown();
     }
 
     #[test]
-    fn same_reference_and_root() -> Result<(), Box<dyn std::error::Error>> {
-        let mut cmd = Command::cargo_bin("dupsrm")?;
+    #[serial]
+    fn dry_run_stdout() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+        let expected = format!(
+            "{}\n",
+            fs::canonicalize(&test_case.file_path_1)
+                .unwrap()
+                .to_str()
+                .unwrap()
+        );
 
-        cmd.arg("./").arg(".");
-        cmd.assert().failure().stderr(predicate::str::contains(
-            "Reference directory must not be identical to root directory",
-        ));
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("-n");
 
-        Ok(())
+        // Check results, only the paths are written to stdout and the log to stderr
+        cmd.assert()
+            .success()
+            .stdout(predicate::eq(expected.as_str()))
+            .stderr(predicate::str::contains("file_test_6.txt (duplicate of "))
+            .stderr(predicate::str::contains("Found 1 duplicates, would free"));
+        assert!(test_case.file_path_1.exists());
+
+        test_case.teardown();
     }
 
     #[test]
     #[serial]
-    fn match_regex() {
+    fn dry_run_sorted() {
         let test_case = CliTestCase::new();
         test_case.startup();
+        let mut expected: Vec<PathBuf> = vec![fs::canonicalize(&test_case.file_path_1).unwrap()];
+        for (i, name) in ["zz.txt", "a/b.txt", "m.txt", "a.txt", "b/a.txt"]
+            .iter()
+            .enumerate()
+        {
+            let path = test_case.reference_dir_path.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, format!("test {} 7", i)).unwrap();
+            expected.push(fs::canonicalize(&path).unwrap());
+        }
+        expected.sort();
 
-        // Check prerequisites
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("-n");
+        let output = cmd.assert().success().get_output().stderr.clone();
+
+        // Check results, the found duplicates are logged in path order
+        let found: Vec<PathBuf> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .filter_map(|line| line.split_once("Found "))
+            .filter_map(|(_, rest)| rest.split_once(" (duplicate of "))
+            .map(|(path, _)| PathBuf::from(path))
+            .collect();
+        assert_eq!(found, expected);
+
+        test_case.teardown();
+    }
+
+    #[rstest]
+    #[case::reference(true, "Reference path")]
+    #[serial]
+    #[case::root(false, "Root path")]
+    #[serial]
+    fn require_dirs(#[case] file_as_reference: bool, #[case] message: &str) {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+        let (reference, root) = match file_as_reference {
+            true => (
+                test_case.file_path_1.clone(),
+                test_case.root_dir_path.clone(),
+            ),
+            false => (
+                test_case.reference_dir_path.clone(),
+                PathBuf::from("test/test.txt"),
+            ),
+        };
+
+        // Execute program, a file is only warned about by default
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&reference).arg(&root).arg("-n");
+        cmd.assert()
+            .success()
+            .stderr(predicate::str::contains("should be a directory"));
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&reference)
+            .arg(&root)
+            .arg("--require-dirs")
+            .arg("--allow-root");
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains(format!("{} ", message)))
+            .stderr(predicate::str::contains("is not a directory"));
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&reference)
+            .arg(&root)
+            .args(["--require-dirs", "--error-format", "json"])
+            .arg("--allow-root");
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("\"kind\":\"NotADirectory\""));
+
+        // Check results
         assert!(test_case.file_path_1.exists());
         assert!(test_case.file_path_2.exists());
-        assert!(test_case.root_dir_path.exists());
-        assert!(test_case.reference_dir_path.exists());
+
+        test_case.teardown();
+    }
+
+    // Without a cache, only files sharing their size and leading bytes are hashed
+    #[rstest]
+    #[case::removed(&[], "2 reference files walked, 2 passed the filters, 1 hashed, 1 root files hashed, 1 duplicates found, 1 removed")]
+    #[serial]
+    #[case::filtered(&["-r", "9.txt$", "-n"], "2 reference files walked, 1 passed the filters, 0 hashed, 0 root files hashed, 0 duplicates found, 0 removed")]
+    #[serial]
+    #[case::unfiltered(&["--cache", "./test/test_summary_cache", "-n"], "2 reference files walked, 2 passed the filters, 2 hashed, 100 root files hashed, 1 duplicates found, 0 removed")]
+    #[serial]
+    fn summary(#[case] args: &[&str], #[case] expected: &str) {
+        let test_case = CliTestCase::new();
+        test_case.startup();
 
         // Execute program
         let mut cmd = match Command::cargo_bin("dupsrm") {
@@ -222,34 +390,164 @@ mod tests {
         };
         cmd.arg(&test_case.reference_dir_path)
             .arg(&test_case.root_dir_path)
-            .arg("-r")
-            .arg("(6.txt)$");
-        cmd.assert().success();
+            .args(args)
+            .arg("--allow-root");
+        cmd.assert()
+            .success()
+            .stderr(predicate::str::contains(format!("Summary: {}", expected)));
 
         // Check results
-        assert!(!test_case.file_path_1.exists());
-        assert!(test_case.file_path_2.exists());
+        assert_eq!(test_case.file_path_1.exists(), args.contains(&"-n"));
 
+        fs::remove_file("./test/test_summary_cache").unwrap_or(());
         test_case.teardown();
     }
 
     #[rstest]
+    #[case::dry_run(&["-n"], 1, 0)]
     #[serial]
-    #[case::sha2_256("SHA2-256")]
+    #[case::removed(&[], 1, 1)]
     #[serial]
-    #[case::sha3_256("SHA3-256")]
+    #[case::filtered(&["-r", "9.txt$", "-n"], 0, 0)]
     #[serial]
-    #[case::sha1("SHA1")]
+    fn json_summary(#[case] args: &[&str], #[case] found: u64, #[case] removed: u64) {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+        let size = fs::metadata(&test_case.file_path_1).unwrap().len();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("--json-summary")
+            .args(args)
+            .arg("--allow-root");
+        let output = cmd.assert().success().get_output().stdout.clone();
+
+        // Check results
+        let summary: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(summary["scanned"], 2);
+        assert_eq!(summary["duplicates_found"], found);
+        assert_eq!(summary["removed"], removed);
+        assert_eq!(summary["bytes_freed"], removed * size);
+        assert_eq!(summary["algorithm"], "SHA2-256");
+        assert_eq!(summary["dry_run"], args.contains(&"-n"));
+
+        test_case.teardown();
+    }
+
+    #[rstest]
+    #[case::found(&[], "1\n")]
     #[serial]
-    #[case::md5("MD5")]
+    #[case::filtered(&["-r", "9.txt$"], "0\n")]
     #[serial]
-    #[case::whirlpool("WHIRLPOOL")]
+    fn count_only(#[case] args: &[&str], #[case] count: &str) {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("--count-only")
+            .args(args);
+        cmd.assert()
+            .success()
+            .stdout(predicate::eq(count))
+            .stderr(predicate::str::contains("INFO").not());
+
+        // Check results, nothing is removed
+        assert!(test_case.file_path_1.exists());
+
+        test_case.teardown();
+    }
+
+    #[rstest]
+    #[case::no("no\n", false)]
     #[serial]
-    #[case::ripemd160("RIPEMD-160")]
+    #[case::yes("yes\n", true)]
     #[serial]
-    #[case::blake256("BLAKE-256")]
+    #[case::short("Y\n", true)]
     #[serial]
-    fn hash_algorithms(#[case] alorithm: &str) {
+    #[case::closed("", false)]
+    #[serial]
+    fn confirm_once(#[case] answer: &str, #[case] removed: bool) {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Execute program, answering the prompt on stdin
+        let mut cmd = match assert_cmd::Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("--confirm-once")
+            .arg("--allow-root")
+            .write_stdin(answer);
+        cmd.assert()
+            .success()
+            .stderr(predicate::str::contains("file_test_6.txt (duplicate of "))
+            .stderr(predicate::str::contains(
+                "Removing 1 duplicates, continue? [y/N]",
+            ));
+
+        // Check results
+        assert_eq!(test_case.file_path_1.exists(), !removed);
+        assert!(test_case.file_path_2.exists());
+
+        test_case.teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn dry_run_print0() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+        let spaced_path = test_case.reference_dir_path.join("file test\n3.txt");
+        fs::write(&spaced_path, "test 3 4").unwrap();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("-n")
+            .arg("--print0");
+        let output = cmd
+            .assert()
+            .success()
+            .stderr(predicate::str::contains("INFO").not())
+            .get_output()
+            .stdout
+            .clone();
+
+        // Check results, paths are sorted and each terminated by NUL
+        let paths: Vec<&[u8]> = output.split(|byte| *byte == b'\0').collect();
+        let expected = [
+            fs::canonicalize(&spaced_path).unwrap(),
+            fs::canonicalize(&test_case.file_path_1).unwrap(),
+        ];
+        assert_eq!(paths.len(), 3);
+        assert_eq!(paths[0], expected[0].to_str().unwrap().as_bytes());
+        assert_eq!(paths[1], expected[1].to_str().unwrap().as_bytes());
+        assert!(paths[2].is_empty());
+        assert!(spaced_path.exists());
+
+        test_case.teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn duplicates_removed() {
         let test_case = CliTestCase::new();
         test_case.startup();
 
@@ -266,8 +564,32 @@ mod tests {
         };
         cmd.arg(&test_case.reference_dir_path)
             .arg(&test_case.root_dir_path)
-            .arg("-a")
-            .arg(alorithm);
+            .arg("--allow-root");
+        cmd.assert().success();
+
+        // Check results
+        assert!(!test_case.file_path_1.exists());
+        assert!(test_case.file_path_2.exists());
+
+        test_case.teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn duplicates_removed_single_thread() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("--threads")
+            .arg("1")
+            .arg("--allow-root");
         cmd.assert().success();
 
         // Check results
@@ -277,24 +599,3216 @@ mod tests {
         test_case.teardown();
     }
 
+    #[rstest]
+    #[serial]
+    #[case::count_exceeded("--max-delete", "0", false)]
+    #[serial]
+    #[case::count_reached("--max-delete", "1", true)]
+    #[serial]
+    #[case::fraction_exceeded("--max-delete-fraction", "0.4", false)]
+    #[serial]
+    #[case::fraction_reached("--max-delete-fraction", "0.5", true)]
+    #[serial]
+    fn max_delete(#[case] flag: &str, #[case] limit: &str, #[case] removed: bool) {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Execute program, one of two reference files is a duplicate
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg(flag)
+            .arg(limit)
+            .arg("--allow-root");
+        match removed {
+            true => cmd.assert().success(),
+            false => cmd
+                .assert()
+                .failure()
+                .stderr(predicate::str::contains("Refusing to process 1 of 2")),
+        };
+
+        // Check results
+        assert_eq!(test_case.file_path_1.exists(), !removed);
+        assert!(test_case.file_path_2.exists());
+
+        test_case.teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn duplicates_trashed() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("--trash")
+            .arg("--allow-root");
+        cmd.assert()
+            .success()
+            .stderr(predicate::str::contains("Trashed file"));
+
+        // Check results
+        assert!(!test_case.file_path_1.exists());
+        assert!(test_case.file_path_2.exists());
+
+        test_case.teardown();
+    }
+
+    #[rstest]
+    #[serial]
+    #[case::outside("./test/test_backup/")]
+    #[serial]
+    #[case::inside_reference("./test/test_reference/backup/")]
+    #[serial]
+    fn duplicates_backed_up(#[case] backup_dir: &str) {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Backup of a previous run colliding with the duplicate
+        let backup_dir_path = PathBuf::from(backup_dir);
+        fs::create_dir_all(&backup_dir_path).unwrap();
+        let previous_path = backup_dir_path.join("file_test_6.txt");
+        fs::write(&previous_path, "test 5 2").unwrap();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("--backup-dir")
+            .arg(&backup_dir_path)
+            .arg("--allow-root");
+        cmd.assert()
+            .success()
+            .stderr(predicate::str::contains("Backed up file"));
+
+        // Check the duplicate is moved next to the previous backup, which is left untouched
+        assert!(!test_case.file_path_1.exists());
+        assert!(test_case.file_path_2.exists());
+        assert!(previous_path.exists());
+        let backup_path = backup_dir_path.join("file_test_6.txt.1");
+        assert_eq!(fs::read_to_string(backup_path).unwrap(), "test 5 2");
+
+        fs::remove_dir_all(&backup_dir_path).unwrap();
+        test_case.teardown();
+    }
+
+    #[rstest]
+    #[serial]
+    #[case::removed(&[], "removed", false)]
+    #[serial]
+    #[case::backed_up(&["--backup-dir", "./test/test_backup/"], "backed up", true)]
+    #[serial]
+    fn removal_logged(#[case] args: &[&str], #[case] action: &str, #[case] moved: bool) {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+        let log_path = PathBuf::from("./test/test_removed.log");
+        let reference_path = test_case.file_path_1.canonicalize().unwrap();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("--log-removed")
+            .arg(&log_path)
+            .args(args)
+            .arg("--allow-root");
+        cmd.assert().success();
+
+        // Check the log holds a single entry matching the removed file
+        let log = fs::read_to_string(&log_path).unwrap();
+        let entries: Vec<RemovalLogEntry> = log
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(entries.len(), 1);
+        assert!(!test_case.file_path_1.exists());
+        assert_eq!(entries[0].reference_path, reference_path);
+        assert_eq!(
+            entries[0].matching_root_path,
+            test_case
+                .root_dir_path
+                .join("dir_5/file_2.txt")
+                .canonicalize()
+                .unwrap()
+        );
+        assert_eq!(entries[0].size, 8);
+        assert_eq!(entries[0].action, action);
+        assert_eq!(
+            entries[0]
+                .destination
+                .as_ref()
+                .is_some_and(|path| path.exists()),
+            moved
+        );
+
+        fs::remove_file(&log_path).unwrap();
+        fs::remove_dir_all("./test/test_backup/").unwrap_or(());
+        test_case.teardown();
+    }
+
+    #[rstest]
+    #[serial]
+    #[case::first("first", "a.txt")]
+    #[serial]
+    #[case::oldest("oldest", "b.txt")]
+    #[serial]
+    #[case::newest("newest", "c.txt")]
+    #[serial]
+    fn deduplicated_within(#[case] keep: &str, #[case] kept: &str) {
+        // Three identical files with distinct modification times and a unique file
+        let dir_path = PathBuf::from("./test/test_within/");
+        fs::create_dir_all(&dir_path).unwrap();
+        let now = SystemTime::now();
+        for (name, age) in [("a.txt", 60), ("b.txt", 120), ("c.txt", 0)] {
+            let file = fs::File::create(dir_path.join(name)).unwrap();
+            (&file).write_all(b"test within").unwrap();
+            file.set_modified(now - Duration::from_secs(age)).unwrap();
+        }
+        fs::write(dir_path.join("unique.txt"), "test unique").unwrap();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&dir_path)
+            .args(["--within", "--keep", keep])
+            .arg("--allow-root");
+        cmd.assert().success();
+
+        // Check only the selected copy and the unique file are left
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            assert_eq!(dir_path.join(name).exists(), name == kept);
+        }
+        assert!(dir_path.join("unique.txt").exists());
+
+        fs::remove_dir_all(&dir_path).unwrap();
+    }
+
+    #[rstest]
+    #[case::files(&[], false)]
+    #[serial]
+    #[case::dirs(&["--dirs"], true)]
+    #[serial]
+    fn dirs(#[case] args: &[&str], #[case] removed_as_unit: bool) {
+        // A copied folder structure and a folder only partially matching it
+        let dir_path = PathBuf::from("./test/test_dirs/");
+        let root_path = dir_path.join("root/");
+        let reference_path = dir_path.join("reference/");
+        for copy_path in [root_path.join("album"), reference_path.join("copy")] {
+            fs::create_dir_all(copy_path.join("b")).unwrap();
+            fs::write(copy_path.join("a.txt"), "test a").unwrap();
+            fs::write(copy_path.join("b/c.txt"), "test c").unwrap();
+        }
+        fs::create_dir_all(reference_path.join("partial")).unwrap();
+        fs::write(reference_path.join("partial/a.txt"), "test a").unwrap();
+        fs::write(reference_path.join("partial/extra.txt"), "test extra").unwrap();
+
+        // Execute program in a dry run first
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&reference_path)
+            .arg(&root_path)
+            .arg("-n")
+            .args(args);
+        let found = predicate::str::contains("Found directory");
+        if removed_as_unit {
+            cmd.assert().success().stderr(found);
+        } else {
+            cmd.assert().success().stderr(found.not());
+        }
+        assert!(reference_path.join("copy/a.txt").exists());
+
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&reference_path)
+            .arg(&root_path)
+            .args(args)
+            .arg("--allow-root");
+        cmd.assert().success();
+
+        // Check the copy is removed as a whole and the partial match is left alone
+        assert!(!reference_path.join("copy/a.txt").exists());
+        assert!(!reference_path.join("copy/b/c.txt").exists());
+        assert_eq!(reference_path.join("copy").exists(), !removed_as_unit);
+        assert_eq!(
+            reference_path.join("partial/a.txt").exists(),
+            removed_as_unit
+        );
+        assert!(reference_path.join("partial/extra.txt").exists());
+        assert!(root_path.join("album/b/c.txt").exists());
+
+        fs::remove_dir_all(&dir_path).unwrap();
+    }
+
+    #[test]
+    #[serial]
+    fn dirs_pruned() {
+        // A copied folder structure holding a hidden file pruned from the walk
+        let dir_path = PathBuf::from("./test/test_dirs_pruned/");
+        let root_path = dir_path.join("root/");
+        let reference_path = dir_path.join("reference/");
+        for copy_path in [root_path.join("album"), reference_path.join("copy")] {
+            fs::create_dir_all(&copy_path).unwrap();
+            fs::write(copy_path.join("a.txt"), "test a").unwrap();
+        }
+        let hidden_path = reference_path.join("copy/.hidden.txt");
+        fs::write(&hidden_path, "test hidden").unwrap();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&reference_path)
+            .arg(&root_path)
+            .args(["--dirs", "--ignore-hidden"])
+            .arg("--allow-root");
+        cmd.assert()
+            .success()
+            .stderr(predicate::str::contains(".hidden.txt wasn't compared"));
+
+        // Check the directory is left alone instead of being removed partially
+        assert!(reference_path.join("copy/a.txt").exists());
+        assert!(hidden_path.exists());
+
+        fs::remove_dir_all(&dir_path).unwrap();
+    }
+
+    #[test]
+    #[serial]
+    fn dirs_skipped_parent() {
+        // A copied folder structure whose top folder holds a hidden file pruned from the walk
+        let dir_path = PathBuf::from("./test/test_dirs_skipped_parent/");
+        let root_path = dir_path.join("root/");
+        let reference_path = dir_path.join("reference/");
+        for copy_path in [root_path.join("album"), reference_path.join("copy")] {
+            fs::create_dir_all(copy_path.join("b")).unwrap();
+            fs::write(copy_path.join("a.txt"), "test a").unwrap();
+            fs::write(copy_path.join("b/c.txt"), "test c").unwrap();
+        }
+        let hidden_path = reference_path.join("copy/.hidden.txt");
+        fs::write(&hidden_path, "test hidden").unwrap();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&reference_path)
+            .arg(&root_path)
+            .args(["--dirs", "--ignore-hidden"])
+            .arg("--allow-root");
+        cmd.assert()
+            .success()
+            .stderr(predicate::str::contains(".hidden.txt wasn't compared"));
+
+        // Check the matching subdirectory is removed as a unit in place of its skipped parent
+        assert!(!reference_path.join("copy/b").exists());
+        assert!(reference_path.join("copy/a.txt").exists());
+        assert!(hidden_path.exists());
+        assert!(root_path.join("album/b/c.txt").exists());
+
+        fs::remove_dir_all(&dir_path).unwrap();
+    }
+
+    #[test]
+    fn backup_conflicts_with_trash() {
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.args([
+            "--trash",
+            "--backup-dir",
+            "test/test_backup",
+            "test",
+            "test",
+        ]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("cannot be used with"));
+    }
+
+    #[test]
+    #[serial]
+    fn duplicates_batch_unlinked() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("--batch-unlink")
+            .arg("--allow-root");
+        cmd.assert().success();
+
+        // Check results
+        assert!(!test_case.file_path_1.exists());
+        assert!(test_case.file_path_2.exists());
+
+        test_case.teardown();
+    }
+
+    #[test]
+    fn remove_batched_files() {
+        let dir_path = PathBuf::from("./test/test_batch/");
+        fs::create_dir_all(dir_path.join("a")).unwrap();
+        fs::create_dir_all(dir_path.join("b")).unwrap();
+        let paths = [
+            dir_path.join("a/1.txt"),
+            dir_path.join("b/2.txt"),
+            dir_path.join("a/3.txt"),
+        ];
+        paths
+            .iter()
+            .for_each(|path| fs::write(path, "test").unwrap());
+        let missing_path = dir_path.join("b/missing.txt");
+
+        let results = remove_batched(
+            &[
+                paths[0].as_path(),
+                missing_path.as_path(),
+                paths[1].as_path(),
+                paths[2].as_path(),
+            ],
+            true,
+        );
+
+        // Check the results are reported in order of the paths
+        assert!(results[0].as_ref().unwrap().is_ok());
+        assert_eq!(
+            results[1].as_ref().unwrap().as_ref().unwrap_err().kind(),
+            std::io::ErrorKind::NotFound
+        );
+        assert!(results[2].as_ref().unwrap().is_ok() && results[3].as_ref().unwrap().is_ok());
+        assert!(paths.iter().all(|path| !path.exists()));
+
+        // Without keep going, no file is unlinked after the first failure of its directory
+        fs::write(&paths[0], "test").unwrap();
+        let missing_path = dir_path.join("a/missing.txt");
+        let results = remove_batched(&[missing_path.as_path(), paths[0].as_path()], false);
+        assert!(results[0].as_ref().unwrap().is_err());
+        assert!(results[1].is_none());
+        assert!(paths[0].exists());
+
+        fs::remove_dir_all(&dir_path).unwrap();
+    }
+
+    #[rstest]
+    #[serial]
+    #[case::dry_run(true, "Found 2 duplicates, would free 16 B")]
+    #[serial]
+    #[case::removal(false, "Removed 2 files, freed 16 B")]
+    #[serial]
+    fn freed_summary(#[case] dry_run: bool, #[case] summary: &str) {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+        let mut file =
+            fs::File::create(test_case.reference_dir_path.join("file_test_7.txt")).unwrap();
+        let _ = file.write_all("test 3 4".as_bytes());
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("--allow-root");
+        if dry_run {
+            cmd.arg("-n");
+        }
+
+        // Check results
+        cmd.assert()
+            .success()
+            .stderr(predicate::str::contains(summary));
+
+        test_case.teardown();
+    }
+
+    #[rstest]
+    #[case::bytes(512, "512 B")]
+    #[case::kibibytes(1536, "1.5 KiB")]
+    #[case::gibibytes(1395864371, "1.3 GiB")]
+    fn format_byte_sizes(#[case] bytes: u64, #[case] expected: &str) {
+        assert_eq!(format_bytes(bytes), expected);
+    }
+
+    #[test]
+    fn file_doesnt_exist() -> Result<(), Box<dyn std::error::Error>> {
+        let mut cmd = Command::cargo_bin("dupsrm")?;
+
+        cmd.arg("./dsdfgdf").arg(".").arg("--allow-root");
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("No such file or directory"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn same_reference_and_root() -> Result<(), Box<dyn std::error::Error>> {
+        let mut cmd = Command::cargo_bin("dupsrm")?;
+
+        cmd.arg("./").arg(".").arg("--allow-root");
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "Reference directory must not be identical to root directory",
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn same_reference_and_root_json() -> Result<(), Box<dyn std::error::Error>> {
+        let mut cmd = Command::cargo_bin("dupsrm")?;
+
+        cmd.arg("./")
+            .arg(".")
+            .args(["--error-format", "json", "--allow-root"]);
+        let output = cmd
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("ERROR").not())
+            .get_output()
+            .stderr
+            .clone();
+
+        // The error is the last line, after the logged messages
+        let line = String::from_utf8(output)?
+            .lines()
+            .last()
+            .unwrap()
+            .to_string();
+        let error: serde_json::Value = serde_json::from_str(&line)?;
+        assert_eq!(error["kind"], "SameDirectory");
+        assert_eq!(
+            error["error"],
+            "Reference directory must not be identical to root directory"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn error_variants() {
+        let dir = Path::new("test").canonicalize().unwrap();
+        let other = Path::new("tests").canonicalize().unwrap();
+        assert!(matches!(
+            check_distinct(&dir, &[other.clone(), dir.clone()]),
+            Err(DupsrmError::SameDirectory)
+        ));
+        assert!(check_distinct(&dir, &[other]).is_ok());
+        assert!(matches!(
+            build_regex("(", false),
+            Err(DupsrmError::InvalidRegex(_))
+        ));
+        assert!(build_regex("(6.txt)$", false).is_ok());
+    }
+
+    #[rstest]
+    #[case::regex("-r")]
+    #[case::root_regex("--root-regex")]
+    fn invalid_regex(#[case] flag: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut cmd = Command::cargo_bin("dupsrm")?;
+
+        cmd.arg("test").arg("tests").arg(flag).arg("(");
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains(
+                "Invalid regular expression: unclosed group",
+            ))
+            .stderr(predicate::str::contains("panicked").not());
+
+        // The error is reported as a JSON object, without the logged error before it
+        let mut cmd = Command::cargo_bin("dupsrm")?;
+        cmd.arg("test")
+            .arg("tests")
+            .arg(flag)
+            .arg("(")
+            .args(["--error-format", "json"]);
+        let output = cmd
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("ERROR").not())
+            .get_output()
+            .stderr
+            .clone();
+        let line = String::from_utf8(output)?
+            .lines()
+            .last()
+            .unwrap()
+            .to_string();
+        let error: serde_json::Value = serde_json::from_str(&line)?;
+        assert_eq!(error["kind"], "InvalidRegex");
+        assert_eq!(error["error"], "Invalid regular expression: unclosed group");
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[serial]
+    #[case::default(&["-r", "JPG$"], false)]
+    #[serial]
+    #[case::ignore_case(&["-r", "JPG$", "--regex-ignore-case"], true)]
+    #[serial]
+    #[case::inline_flag(&["-r", "(?i)JPG$"], true)]
+    #[serial]
+    fn regex_ignore_case(#[case] regex_args: &[&str], #[case] removed: bool) {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+        let image_path = test_case.reference_dir_path.join("image.jpg");
+        fs::write(&image_path, "test 5 3").unwrap();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .args(regex_args)
+            .arg("--allow-root");
+        cmd.assert().success();
+
+        // Check results, only the file matching the regular expression is removed
+        assert_eq!(image_path.exists(), !removed);
+        assert!(test_case.file_path_1.exists());
+
+        test_case.teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn match_regex_relative() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+        let nested_dir = test_case.reference_dir_path.join("dir_3");
+        fs::create_dir(&nested_dir).unwrap();
+        let nested_path = nested_dir.join("file_test_3.txt");
+        fs::write(&nested_path, "test 5 3").unwrap();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("-r")
+            .arg("^dir_3/")
+            .arg("--allow-root");
+        cmd.assert().success();
+
+        // Check results, the path is anchored at the reference directory
+        assert!(!nested_path.exists());
+        assert!(test_case.file_path_1.exists());
+
+        test_case.teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn match_regex() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Check prerequisites
+        assert!(test_case.file_path_1.exists());
+        assert!(test_case.file_path_2.exists());
+        assert!(test_case.root_dir_path.exists());
+        assert!(test_case.reference_dir_path.exists());
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("-r")
+            .arg("(6.txt)$")
+            .arg("--allow-root");
+        cmd.assert().success();
+
+        // Check results
+        assert!(!test_case.file_path_1.exists());
+        assert!(test_case.file_path_2.exists());
+
+        test_case.teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn no_reference_files_matched() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("-r")
+            .arg("no_such_file$")
+            .arg("--allow-root");
+        cmd.assert().success().stderr(predicate::str::contains(
+            "No reference files matched the given filters",
+        ));
+
+        // Check results
+        assert!(test_case.file_path_1.exists());
+        assert!(test_case.file_path_2.exists());
+
+        test_case.teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn empty_root_dir() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+        let empty_dir = PathBuf::from("./test/test_empty_root/");
+        fs::create_dir_all(&empty_dir).unwrap();
+        // Empty files are walked but never hashed into the root index
+        fs::write(empty_dir.join("empty.txt"), "").unwrap();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&empty_dir)
+            .arg("--allow-root");
+        cmd.assert().success().stderr(predicate::str::contains(
+            "Root directory contains no hashable files, nothing can be matched",
+        ));
+
+        // Check results
+        assert!(test_case.file_path_1.exists());
+        assert!(test_case.file_path_2.exists());
+
+        fs::remove_dir_all(&empty_dir).unwrap();
+        test_case.teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn find_duplicates_library() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        let duplicates = find_duplicates(
+            &test_case.root_dir_path,
+            &test_case.reference_dir_path,
+            &HashAlgorithm::SHA2_256,
+            None,
+        )
+        .unwrap();
+
+        // Check results, nothing is removed by the library call
+        let reference = fs::canonicalize(&test_case.file_path_1).unwrap();
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].reference, reference);
+        assert!(is_identical(&duplicates[0].reference, &duplicates[0].root_match).unwrap());
+        assert_eq!(duplicates[0].hash, sha256sum(&reference).unwrap());
+        assert_eq!(duplicates[0].size, fs::metadata(&reference).unwrap().len());
+        assert!(test_case.file_path_1.exists());
+        let regex = Regex::new("(9.txt)$").unwrap();
+        let duplicates = find_duplicates(
+            &test_case.root_dir_path,
+            &test_case.reference_dir_path,
+            &HashAlgorithm::SHA2_256,
+            Some(&regex),
+        )
+        .unwrap();
+        assert!(duplicates.is_empty());
+        // Matched relative to the reference directory, like on the command line
+        let regex = Regex::new("^file_test_6").unwrap();
+        let duplicates = find_duplicates(
+            &test_case.root_dir_path,
+            &test_case.reference_dir_path,
+            &HashAlgorithm::SHA2_256,
+            Some(&regex),
+        )
+        .unwrap();
+        assert_eq!(duplicates.len(), 1);
+
+        test_case.teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn for_each_duplicate_callback() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        let mut duplicates: Vec<Duplicate> = Vec::new();
+        for_each_duplicate(
+            &test_case.root_dir_path,
+            &test_case.reference_dir_path,
+            &HashAlgorithm::SHA2_256,
+            None,
+            |duplicate| duplicates.push(duplicate.clone()),
+        )
+        .unwrap();
+
+        // Check results, the match is reported with its root file and nothing is removed
+        assert_eq!(
+            duplicates,
+            vec![Duplicate {
+                reference: fs::canonicalize(&test_case.file_path_1).unwrap(),
+                root_match: fs::canonicalize(test_case.root_dir_path.join("dir_5/file_2.txt"))
+                    .unwrap(),
+                hash: sha256sum(&test_case.file_path_1).unwrap(),
+                size: "test 5 2".len() as u64,
+            }]
+        );
+        assert!(test_case.file_path_1.exists());
+
+        test_case.teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn hash_match_duplicates_streaming() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+        let algorithm = HashAlgorithm::SHA2_256;
+        let hash_sum = |path: &Path| hash_file(path, &algorithm);
+
+        let root_files = walk_files(&test_case.root_dir_path, &[], &WalkOptions::default());
+        let root_pairs = hash_files(root_files, hash_sum, &algorithm, false);
+        let index = build_index(
+            &root_pairs,
+            KeyedState::random(),
+            &RootPreference::ShortestPath,
+        );
+        let root_paths: HashSet<&PathBuf> = root_pairs.iter().map(|pair| &pair.1).collect();
+        // Reference files reached twice and root files listed as reference files are skipped
+        let mut reference_files =
+            walk_files(&test_case.reference_dir_path, &[], &WalkOptions::default());
+        reference_files.extend(reference_files.clone());
+        reference_files.push(test_case.root_dir_path.join("dir_5/file_2.txt"));
+
+        let mut reference_pairs = hash_files(reference_files.clone(), hash_sum, &algorithm, false);
+        reference_pairs.retain(|pair| !root_paths.contains(&pair.1));
+        reference_pairs.sort_by(|a, b| a.1.cmp(&b.1));
+        reference_pairs.dedup_by(|a, b| a.1 == b.1);
+        let collected = match_duplicates(&index, &reference_pairs, &ByteVerify);
+        let streamed = hash_match_duplicates(
+            &index,
+            reference_files,
+            hash_sum,
+            &algorithm,
+            false,
+            &ByteVerify,
+            &root_paths,
+        );
+        assert_eq!(streamed, collected);
+        assert_eq!(
+            streamed,
+            vec![(
+                sha256sum(&test_case.file_path_1).unwrap(),
+                fs::canonicalize(&test_case.file_path_1).unwrap()
+            )]
+        );
+
+        test_case.teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn file_removed_before_hashing() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+        let algorithm = HashAlgorithm::SHA2_256;
+        let hash_sum = |path: &Path| hash_file(path, &algorithm);
+
+        // Walked paths are hashed as they are, a file gone since the walk is skipped
+        let root_dir = fs::canonicalize(&test_case.root_dir_path).unwrap();
+        let root_files = walk_files(&root_dir, &[], &WalkOptions::default());
+        let removed = root_dir.join("dir_5/file_2.txt");
+        assert!(root_files.contains(&removed));
+        fs::remove_file(&removed).unwrap();
+        let root_pairs = hash_files(root_files.clone(), hash_sum, &algorithm, false);
+        assert_eq!(root_pairs.len(), root_files.len() - 1);
+        assert!(root_pairs.iter().all(|pair| pair.1 != removed));
+        assert!(canonicalize_files(root_files.clone())
+            .iter()
+            .all(|path| path != &removed));
+
+        test_case.teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn prefilter_candidates() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Only the duplicate and its root copy share size and leading bytes
+        let root_files = walk_files(&test_case.root_dir_path, &[], &WalkOptions::default());
+        let reference_files =
+            walk_files(&test_case.reference_dir_path, &[], &WalkOptions::default());
+        let (root_files, reference_files) = prefilter_files(root_files, reference_files);
+        assert_eq!(
+            root_files,
+            vec![test_case.root_dir_path.join("dir_5/file_2.txt")]
+        );
+        assert_eq!(reference_files, vec![test_case.file_path_1.clone()]);
+
+        test_case.teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn plan_candidates_by_size() {
+        let dir_path = PathBuf::from("./test/test_plan/");
+        let (root_dir, reference_dir) = (dir_path.join("root"), dir_path.join("reference"));
+        fs::create_dir_all(&root_dir).unwrap();
+        fs::create_dir_all(&reference_dir).unwrap();
+        // Files of a shared size with different content, and a uniquely-sized file on each side
+        let shared_root = root_dir.join("shared.txt");
+        let shared_reference = reference_dir.join("shared.txt");
+        fs::write(&shared_root, "root").unwrap();
+        fs::write(&shared_reference, "copy").unwrap();
+        fs::write(root_dir.join("unique.txt"), "root only").unwrap();
+        fs::write(reference_dir.join("unique.txt"), "reference only").unwrap();
+
+        let root_files = walk_files(&root_dir, &[], &WalkOptions::default());
+        let reference_files = walk_files(&reference_dir, &[], &WalkOptions::default());
+        let (root_files, reference_files) = plan_candidates(root_files, reference_files);
+        assert_eq!(root_files, vec![shared_root]);
+        assert_eq!(reference_files, vec![shared_reference]);
+
+        fs::remove_dir_all(&dir_path).unwrap();
+    }
+
+    #[rstest]
+    #[case::whole(&[], false)]
+    #[serial]
+    #[case::prefix(&["--hash-bytes", "4"], true)]
+    #[serial]
+    #[case::verified(&["--hash-bytes", "4", "--verify-prefix", "8"], false)]
+    #[serial]
+    fn hash_bytes_prefix(#[case] args: &[&str], #[case] duplicate: bool) {
+        let dir_path = PathBuf::from("./test/test_hash_bytes/");
+        let (root_dir, reference_dir) = (dir_path.join("root"), dir_path.join("reference"));
+        fs::create_dir_all(&root_dir).unwrap();
+        fs::create_dir_all(&reference_dir).unwrap();
+        // Same size and first 4 bytes, different content after them
+        let reference_path = reference_dir.join("file.txt");
+        fs::write(root_dir.join("file.txt"), "test root").unwrap();
+        fs::write(&reference_path, "test copy").unwrap();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&reference_dir)
+            .arg(&root_dir)
+            .args(args)
+            .arg("--allow-root");
+        cmd.assert().success().stderr(
+            predicate::str::contains("Only hashing the first 4 B of files")
+                .count(usize::from(!args.is_empty())),
+        );
+
+        // Check results
+        assert_eq!(reference_path.exists(), !duplicate);
+
+        fs::remove_dir_all(&dir_path).unwrap();
+    }
+
+    #[test]
+    fn hash_bytes_zero() {
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.args(["--hash-bytes", "0", "test", "test"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("must be at least 1 byte"));
+    }
+
+    #[test]
+    #[serial]
+    fn recheck_before_delete() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("--recheck-before-delete")
+            .arg("--allow-root");
+        cmd.assert()
+            .success()
+            .stderr(predicate::str::contains("changed since it was hashed").not());
+
+        // Check results
+        assert!(!test_case.file_path_1.exists());
+        assert!(test_case.file_path_2.exists());
+
+        test_case.teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn recheck_changed() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Execute program, pausing at the prompt after the duplicates were matched
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        let mut child = cmd
+            .arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .args(["--recheck-before-delete", "--confirm-once"])
+            .arg("--allow-root")
+            .stdin(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut stderr = child.stderr.take().unwrap();
+        let mut output: Vec<u8> = Vec::new();
+        let mut byte = [0u8; 1];
+        while !output.ends_with(b"[y/N] ") {
+            assert_eq!(stderr.read(&mut byte).unwrap(), 1);
+            output.push(byte[0]);
+        }
+
+        // The duplicate changes before it is processed
+        fs::write(&test_case.file_path_1, "test 5 2 changed").unwrap();
+        child.stdin.take().unwrap().write_all(b"y\n").unwrap();
+        stderr.read_to_end(&mut output).unwrap();
+        assert!(child.wait().unwrap().success());
+        assert!(String::from_utf8_lossy(&output).contains("changed since it was hashed"));
+
+        // Check results, the changed file is kept
+        assert!(test_case.file_path_1.exists());
+        assert!(test_case.file_path_2.exists());
+
+        test_case.teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn recheck_unchanged() {
+        let dir_path = PathBuf::from("./test/test_recheck/");
+        fs::create_dir_all(&dir_path).unwrap();
+        let file_path = dir_path.join("file.txt");
+        fs::write(&file_path, "test").unwrap();
+        let hash_sum = |path: &Path| hash_file(path, &HashAlgorithm::SHA2_256);
+        let hash = hash_sum(&file_path).unwrap();
+        assert!(is_unchanged(&file_path, &hash, hash_sum).unwrap());
+
+        // Replaced between matching and processing
+        fs::write(&file_path, "tset").unwrap();
+        assert!(!is_unchanged(&file_path, &hash, hash_sum).unwrap());
+        fs::remove_file(&file_path).unwrap();
+        assert!(is_unchanged(&file_path, &hash, hash_sum).is_err());
+
+        fs::remove_dir_all(&dir_path).unwrap();
+    }
+
+    #[test]
+    #[serial]
+    fn prefer_root() {
+        let dir_path = PathBuf::from("./test/test_prefer/");
+        fs::create_dir_all(dir_path.join("nested")).unwrap();
+        let short_path = dir_path.join("a.txt");
+        let old_path = dir_path.join("nested/old.txt");
+        let linked_path = dir_path.join("nested/linked.txt");
+        let new_path = dir_path.join("nested/new.txt");
+        for path in [&short_path, &old_path, &linked_path, &new_path] {
+            fs::write(path, "test").unwrap();
+        }
+        for (path, modified) in [
+            (&old_path, SystemTime::now() - Duration::from_secs(3600)),
+            (&new_path, SystemTime::now() + Duration::from_secs(3600)),
+        ] {
+            fs::File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+        }
+        fs::hard_link(&linked_path, dir_path.join("link.txt")).unwrap();
+
+        let hash = sha256sum(&short_path).unwrap();
+        let pairs: Vec<(Vec<u8>, PathBuf)> = [&linked_path, &old_path, &new_path, &short_path]
+            .iter()
+            .map(|path| (hash.clone(), path.to_path_buf()))
+            .collect();
+        for (preference, expected) in [
+            (RootPreference::ShortestPath, &short_path),
+            (RootPreference::Oldest, &old_path),
+            (RootPreference::Newest, &new_path),
+            (RootPreference::MostLinked, &linked_path),
+        ] {
+            let index = build_index(&pairs, KeyedState::random(), &preference);
+            assert_eq!(index.len(), 1);
+            assert_eq!(index.get(&hash).unwrap(), &expected);
+        }
+
+        fs::remove_dir_all(&dir_path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[rstest]
+    #[serial]
+    #[case::oldest("oldest", "dir_5/file_2.txt")]
+    #[serial]
+    #[case::newest("newest", "dir_copy/file_2.txt")]
+    #[serial]
+    fn prefer_link_target(#[case] prefer: &str, #[case] expected: &str) {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Newer second root copy of the duplicate
+        let copy_dir_path = test_case.root_dir_path.join("dir_copy");
+        fs::create_dir(&copy_dir_path).unwrap();
+        let copy = fs::File::create(copy_dir_path.join("file_2.txt")).unwrap();
+        (&copy).write_all(b"test 5 2").unwrap();
+        copy.set_modified(SystemTime::now() + Duration::from_secs(3600))
+            .unwrap();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .args(["--symlink", "--prefer", prefer])
+            .arg("--allow-root");
+        cmd.assert().success();
+
+        // Check the link points to the preferred root copy
+        assert_eq!(
+            fs::read_link(&test_case.file_path_1).unwrap(),
+            test_case
+                .root_dir_path
+                .join(expected)
+                .canonicalize()
+                .unwrap()
+        );
+
+        test_case.teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn checksums_written() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+        let checksums_path = PathBuf::from("./test/test_checksums.sha256");
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("-n")
+            .arg("--write-checksums")
+            .arg(&checksums_path);
+        cmd.assert().success();
+
+        // Check each line holds the independently computed hash and the relative path
+        let checksums = fs::read_to_string(&checksums_path).unwrap();
+        let lines: Vec<&str> = checksums.lines().collect();
+        assert_eq!(lines.len(), 2);
+        for (line, path) in lines
+            .iter()
+            .zip([&test_case.file_path_1, &test_case.file_path_2])
+        {
+            let expected = format!(
+                "{}  {}",
+                to_hex(&sha256sum(path).unwrap()),
+                path.file_name().unwrap().to_str().unwrap()
+            );
+            assert_eq!(*line, expected);
+        }
+
+        fs::remove_file(&checksums_path).unwrap();
+        test_case.teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn outputs_excluded() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+        // A removal log inside the reference directory, holding the content of a root file
+        let log_path = test_case.reference_dir_path.join("removed.log");
+        fs::write(&log_path, "test 5 3").unwrap();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("--log-removed")
+            .arg(&log_path)
+            .arg("--allow-root");
+        cmd.assert().success();
+
+        // Check the log is not processed as a duplicate
+        assert!(!test_case.file_path_1.exists());
+        assert!(log_path.exists());
+
+        test_case.teardown();
+    }
+
+    /// Write a checksum file of the root files with paths relative to the root directory
+    fn write_root_checksums(test_case: &CliTestCase, checksums_path: &Path) {
+        let mut checksums = String::new();
+        for i in 0..10 {
+            for j in 0..10 {
+                let relative_path = format!("dir_{}/file_{}.txt", i, j);
+                let hash = sha256sum(&test_case.root_dir_path.join(&relative_path)).unwrap();
+                checksums.push_str(&format!("{}  {}\n", to_hex(&hash), relative_path));
+            }
+        }
+        fs::write(checksums_path, checksums).unwrap();
+    }
+
+    #[test]
+    #[serial]
+    fn root_checksums_loaded() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+        let checksums_path = PathBuf::from("./test/test_root.sha256");
+        write_root_checksums(&test_case, &checksums_path);
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("--root-checksums")
+            .arg(&checksums_path)
+            .arg("--allow-root");
+        cmd.assert()
+            .success()
+            .stderr(predicate::str::contains("Root files: 100 loaded"));
+
+        // Check results
+        assert!(!test_case.file_path_1.exists());
+        assert!(test_case.file_path_2.exists());
+
+        fs::remove_file(&checksums_path).unwrap();
+        test_case.teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn root_checksums_empty() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+        // The only listed root file no longer exists, so nothing is left to match against
+        let checksums_path = PathBuf::from("./test/test_root.sha256");
+        let hash = sha256sum(&test_case.file_path_1).unwrap();
+        fs::write(
+            &checksums_path,
+            format!("{}  dir_0/missing.txt\n", to_hex(&hash)),
+        )
+        .unwrap();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("--root-checksums")
+            .arg(&checksums_path)
+            .arg("--allow-root");
+        cmd.assert().success().stderr(predicate::str::contains(
+            "Root directory contains no hashable files, nothing can be matched",
+        ));
+
+        // Check results
+        assert!(test_case.file_path_1.exists());
+        assert!(test_case.file_path_2.exists());
+
+        fs::remove_file(&checksums_path).unwrap();
+        test_case.teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn root_checksums_digest_mismatch() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+        let checksums_path = PathBuf::from("./test/test_root.sha256");
+        write_root_checksums(&test_case, &checksums_path);
+
+        // Execute program with an algorithm producing shorter digests
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .args(["-a", "MD5", "--root-checksums"])
+            .arg(&checksums_path)
+            .arg("--allow-root");
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "holds 32-byte digests, but MD5 produces 16-byte digests",
+        ));
+
+        // Check no file is removed
+        assert!(test_case.file_path_1.exists());
+
+        fs::remove_file(&checksums_path).unwrap();
+        test_case.teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn timings() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("-n")
+            .arg("--timings");
+
+        // Check results
+        let mut assert = cmd
+            .assert()
+            .success()
+            .stderr(predicate::str::contains("Hash throughput: ").not());
+        for phase in ["walk", "hash-root", "hash-reference", "match", "act"] {
+            assert = assert.stderr(predicate::str::contains(format!("Timing {}: ", phase)));
+        }
+
+        test_case.teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn verbose_throughput() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("-n")
+            .arg("--verbose");
+
+        // Check results
+        cmd.assert()
+            .success()
+            .stderr(predicate::str::contains("Hash throughput: "))
+            .stderr(predicate::str::contains("Timing ").not());
+
+        test_case.teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn json_report() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("-n")
+            .arg("--format")
+            .arg("json");
+        let output = cmd.assert().success().get_output().stdout.clone();
+
+        // Check results
+        let report: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        let entries = report.as_array().unwrap();
+        assert_eq!(entries.len(), 1);
+        let reference = fs::canonicalize(&test_case.file_path_1).unwrap();
+        let root = fs::canonicalize(test_case.root_dir_path.join("dir_5/file_2.txt")).unwrap();
+        assert_eq!(entries[0]["reference_path"], reference.to_str().unwrap());
+        assert_eq!(entries[0]["matching_root_path"], root.to_str().unwrap());
+        assert_eq!(
+            entries[0]["hash"],
+            to_hex(&sha256sum(&test_case.file_path_1).unwrap())
+        );
+        assert_eq!(entries[0]["size"], 8);
+        assert_eq!(entries[0]["removed"], false);
+
+        test_case.teardown();
+    }
+
+    #[rstest]
+    #[serial]
+    #[case::dry_run(&["-n"], false)]
+    #[serial]
+    #[case::removal(&[], true)]
+    #[serial]
+    fn csv_report(#[case] args: &[&str], #[case] removed: bool) {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+        let quoted_path = test_case.reference_dir_path.join("dup, \"quoted\".txt");
+        fs::write(&quoted_path, "test 3 4").unwrap();
+        let quoted = fs::canonicalize(&quoted_path).unwrap();
+        let reference = fs::canonicalize(&test_case.file_path_1).unwrap();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .args(args)
+            .arg("--format")
+            .arg("csv")
+            .arg("--allow-root");
+        let output = cmd.assert().success().get_output().stdout.clone();
+
+        // Check results, rows are sorted by the reference path
+        let mut reader = csv::Reader::from_reader(output.as_slice());
+        assert_eq!(
+            reader.headers().unwrap().iter().collect::<Vec<&str>>(),
+            vec![
+                "reference_path",
+                "root_path",
+                "hash_hex",
+                "size_bytes",
+                "removed"
+            ]
+        );
+        let rows: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+        assert_eq!(rows.len(), 2);
+        let root = fs::canonicalize(test_case.root_dir_path.join("dir_3/file_4.txt")).unwrap();
+        assert_eq!(&rows[0][0], quoted.to_str().unwrap());
+        assert_eq!(&rows[0][1], root.to_str().unwrap());
+        assert_eq!(&rows[1][0], reference.to_str().unwrap());
+        assert_eq!(
+            &rows[1][2],
+            to_hex(&sha256sum(&test_case.root_dir_path.join("dir_5/file_2.txt")).unwrap())
+        );
+        assert_eq!(&rows[1][3], "8");
+        assert_eq!(&rows[1][4], removed.to_string());
+        assert_eq!(quoted_path.exists(), !removed);
+
+        test_case.teardown();
+    }
+
+    #[test]
+    fn merge_reports() {
+        let report_path_1 = PathBuf::from("./test/test_report_1.json");
+        let report_path_2 = PathBuf::from("./test/test_report_2.json");
+        fs::write(
+            &report_path_1,
+            r#"[
+                {"reference_path": "/ref/a", "matching_root_path": "/root/a", "hash": "aa", "size": 10, "removed": true},
+                {"reference_path": "/ref/b", "matching_root_path": "/root/b", "hash": "bb", "size": 5, "removed": false}
+            ]"#,
+        )
+        .unwrap();
+        fs::write(
+            &report_path_2,
+            r#"[
+                {"reference_path": "/ref/c", "matching_root_path": "/root/a", "hash": "aa", "size": 10, "removed": true},
+                {"reference_path": "/ref/d", "matching_root_path": "/root/d", "hash": "dd", "size": 7, "removed": true}
+            ]"#,
+        )
+        .unwrap();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg("--merge-reports")
+            .arg(&report_path_1)
+            .arg(&report_path_2);
+
+        // Check results
+        cmd.assert()
+            .success()
+            .stderr(predicate::str::contains(
+                "Merged 2 reports: 3 files removed, freed 27 B",
+            ))
+            .stderr(predicate::str::contains(
+                "Most duplicated content: aa (2 files)",
+            ));
+
+        fs::remove_file(&report_path_1).unwrap();
+        fs::remove_file(&report_path_2).unwrap();
+    }
+
+    #[test]
+    #[serial]
+    fn base_dir() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Execute program from another working directory with paths relative to the base
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.current_dir(std::env::temp_dir())
+            .arg("--base-dir")
+            .arg(std::env::current_dir().unwrap())
+            .arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("--allow-root");
+        cmd.assert().success();
+
+        // Check results
+        assert!(!test_case.file_path_1.exists());
+        assert!(test_case.file_path_2.exists());
+
+        test_case.teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn report_memory() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("-n")
+            .arg("--report-memory");
+
+        // Check results, the root tree contains 100 files with distinct content
+        cmd.assert()
+            .success()
+            .stderr(predicate::str::contains("Index memory: 100 entries"))
+            .stderr(predicate::str::contains("Buffer memory:"));
+
+        test_case.teardown();
+    }
+
+    #[rstest]
+    #[case::older_than(&["--older-than", "7d"], [true, false, false])]
+    #[serial]
+    #[case::newer_than(&["--newer-than", "24h"], [false, true, true])]
+    #[serial]
+    #[case::window(&["--older-than", "1h", "--newer-than", "30d"], [true, false, true])]
+    #[serial]
+    fn modified_age(#[case] args: &[&str], #[case] kept: [bool; 3]) {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Duplicates modified just now, ten days and a year ago
+        let now = SystemTime::now();
+        let old_path = test_case.reference_dir_path.join("file_test_old.txt");
+        let ancient_path = test_case.reference_dir_path.join("file_test_ancient.txt");
+        for (path, content, age) in [
+            (&old_path, "test 5 3", 10 * 86400),
+            (&ancient_path, "test 5 4", 365 * 86400),
+        ] {
+            fs::File::create(path)
+                .unwrap()
+                .write_all(content.as_bytes())
+                .unwrap();
+            fs::File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(now - Duration::from_secs(age))
+                .unwrap();
+        }
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .args(args)
+            .arg("--allow-root");
+        cmd.assert().success();
+
+        // Check results, only files within the window are removed
+        assert_eq!(
+            [&test_case.file_path_1, &old_path, &ancient_path].map(|path| path.exists()),
+            kept
+        );
+
+        test_case.teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn since_marker() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Create a marker older than a new duplicate but newer than the existing duplicate
+        let now = SystemTime::now();
+        let marker_path = PathBuf::from("./test/test_marker");
+        fs::File::create(&marker_path)
+            .unwrap()
+            .set_modified(now - Duration::from_secs(60))
+            .unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&test_case.file_path_1)
+            .unwrap()
+            .set_modified(now - Duration::from_secs(3600))
+            .unwrap();
+        let new_file_path = test_case.reference_dir_path.join("file_test_7.txt");
+        let mut file = fs::File::create(&new_file_path).unwrap();
+        let _ = file.write_all("test 3 4".as_bytes());
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("--since")
+            .arg(&marker_path)
+            .arg("--touch-marker")
+            .arg("--allow-root");
+        cmd.assert().success();
+
+        // Check results
+        assert!(test_case.file_path_1.exists());
+        assert!(test_case.file_path_2.exists());
+        assert!(!new_file_path.exists());
+        let marker_time = fs::metadata(&marker_path).unwrap().modified().unwrap();
+        assert!(marker_time > now - Duration::from_secs(60));
+
+        fs::remove_file(&marker_path).unwrap();
+        test_case.teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn dot_graph() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+        let dot_path = PathBuf::from("./test/test_graph.dot");
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("-n")
+            .arg("--dot")
+            .arg(&dot_path);
+        cmd.assert().success();
+
+        // Check results, file_test_6.txt duplicates dir_5/file_2.txt
+        let reference = fs::canonicalize(&test_case.file_path_1).unwrap();
+        let root = fs::canonicalize(test_case.root_dir_path.join("dir_5/file_2.txt")).unwrap();
+        let dot = fs::read_to_string(&dot_path).unwrap();
+        assert!(dot.starts_with("graph duplicates {"));
+        assert_eq!(dot.matches("subgraph cluster_").count(), 1);
+        assert!(dot.lines().any(|line| line.contains(" -- ")
+            && line.contains(reference.to_str().unwrap())
+            && line.contains(root.to_str().unwrap())));
+
+        fs::remove_file(&dot_path).unwrap();
+        test_case.teardown();
+    }
+
+    #[rstest]
+    #[case::sibling_prefix("/data/reference_backup", "/data/ref", false)]
+    #[case::equal("/data/ref", "/data/ref", true)]
+    #[case::nested("/data/ref/photos/a.jpg", "/data/ref", true)]
+    #[case::trailing_separator("/data/ref/a.jpg", "/data/ref/", true)]
+    #[case::parent("/data", "/data/ref", false)]
+    fn subdirectory(#[case] entry: &str, #[case] reference: &str, #[case] expected: bool) {
+        assert_eq!(
+            is_subdirectory(Path::new(entry), Path::new(reference)),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case::user(false, false, false, true)]
+    #[case::elevated(true, false, false, false)]
+    #[case::elevated_allowed(true, true, false, true)]
+    #[case::elevated_dry_run(true, false, true, true)]
+    fn remove_privileges(
+        #[case] elevated: bool,
+        #[case] allow_root: bool,
+        #[case] dry_run: bool,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(may_remove(elevated, allow_root, dry_run), expected);
+    }
+
+    #[test]
+    fn identical_files() {
+        let path_a = PathBuf::from("./test/test_identical_a.txt");
+        let path_b = PathBuf::from("./test/test_identical_b.txt");
+        fs::write(&path_a, "test 1").unwrap();
+        fs::write(&path_b, "test 2").unwrap();
+
+        // Files of equal size but different content must not be treated as identical
+        assert!(!is_identical(&path_a, &path_b).unwrap());
+        assert!(!is_identical(&path_a, Path::new("test/test.txt")).unwrap());
+        fs::write(&path_b, "test 1").unwrap();
+        assert!(is_identical(&path_a, &path_b).unwrap());
+
+        fs::remove_file(&path_a).unwrap();
+        fs::remove_file(&path_b).unwrap();
+    }
+
+    #[test]
+    #[serial]
+    fn verify_manifest() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Manifest with an unchanged, a changed and a missing file
+        let manifest_path = PathBuf::from("./test/test_manifest.sha256");
+        let unchanged = sha256sum(&test_case.file_path_1).unwrap();
+        let manifest = format!(
+            "{}  file_test_6.txt\n{}  file_test_9.txt\n{}  missing.txt\n",
+            to_hex(&unchanged),
+            to_hex(&sha256sum(Path::new("test/test.txt")).unwrap()),
+            to_hex(&unchanged),
+        );
+        fs::write(&manifest_path, manifest).unwrap();
+        let new_file_path = test_case.reference_dir_path.join("file_test_new.txt");
+        fs::write(&new_file_path, "new").unwrap();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg("--verify-manifest")
+            .arg(&manifest_path)
+            .arg("--allow-root");
+
+        // Check results
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("1 missing, 1 changed, 1 new"))
+            .stderr(predicate::str::is_match("Missing .*missing.txt").unwrap())
+            .stderr(predicate::str::is_match("Changed .*file_test_9.txt").unwrap())
+            .stderr(predicate::str::is_match("New .*file_test_new.txt").unwrap());
+        assert!(test_case.file_path_1.exists());
+        assert!(test_case.file_path_2.exists());
+
+        fs::remove_file(&manifest_path).unwrap();
+        test_case.teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn verify_manifest_unreadable() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+        let reference_dir = fs::canonicalize(&test_case.reference_dir_path).unwrap();
+        let file_path = fs::canonicalize(&test_case.file_path_1).unwrap();
+        let manifest = vec![(sha256sum(&file_path).unwrap(), file_path.clone())];
+
+        // A listed file failing to hash is unreadable, not missing
+        let report = dupsrm::manifest::verify_manifest(&manifest, &reference_dir, &[], |path| {
+            match path == file_path {
+                true => Err(std::io::Error::other("read failed")),
+                false => sha256sum(path),
+            }
+        });
+        assert!(report.missing.is_empty());
+        assert_eq!(report.unreadable.len(), 1);
+        assert_eq!(report.unreadable[0].0, file_path);
+        assert_eq!(report.unreadable[0].1.to_string(), "read failed");
+        assert!(!report.is_clean());
+
+        test_case.teardown();
+    }
+
+    #[rstest]
+    #[serial]
+    #[case::unlimited(&[], false)]
+    #[serial]
+    #[case::min_size(&["--min-size", "1KB"], true)]
+    #[serial]
+    #[case::max_size(&["--max-size", "0"], true)]
+    #[serial]
+    #[case::in_range(&["--min-size", "1", "--max-size", "1K"], false)]
+    #[serial]
+    fn size_filter(#[case] size_args: &[&str], #[case] kept: bool) {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+        let small_file_path = test_case.reference_dir_path.join("file_small.txt");
+        fs::write(&small_file_path, "x").unwrap();
+        fs::write(test_case.root_dir_path.join("dir_0/file_small.txt"), "x").unwrap();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .args(size_args)
+            .arg("--allow-root");
+        cmd.assert().success();
+
+        // Check results
+        assert_eq!(small_file_path.exists(), kept);
+        assert!(test_case.file_path_2.exists());
+
+        test_case.teardown();
+    }
+
+    #[cfg(unix)]
+    #[rstest]
+    #[serial]
+    #[case::verified(&[], true)]
+    #[serial]
+    #[case::trusted(&["--no-verify"], false)]
+    #[serial]
+    #[case::prefix(&["--verify-prefix", "2"], true)]
+    #[serial]
+    #[case::non_cryptographic(&["--no-verify", "-a", "XXH3"], true)]
+    #[serial]
+    fn trust_attr(#[case] verify_args: &[&str], #[case] kept: bool) {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Files with different content but the same trusted hash attribute
+        let reference_path = test_case.reference_dir_path.join("file_attr.txt");
+        let root_path = test_case.root_dir_path.join("dir_0/file_attr.txt");
+        fs::write(&reference_path, "reference").unwrap();
+        fs::write(&root_path, "root").unwrap();
+        xattr::set(&reference_path, "user.dupsrm.hash", b"0123abcd").unwrap();
+        xattr::set(&root_path, "user.dupsrm.hash", b"0123abcd").unwrap();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("--trust-attr")
+            .arg("user.dupsrm.hash")
+            .args(verify_args)
+            .arg("--allow-root");
+        cmd.assert().success();
+
+        // Check results, files without the attribute are not matched
+        assert_eq!(reference_path.exists(), kept);
+        assert!(test_case.file_path_1.exists());
+        assert!(test_case.file_path_2.exists());
+
+        test_case.teardown();
+    }
+
+    #[rstest]
+    #[serial]
+    #[case::reference("reference", true)]
+    #[serial]
+    #[case::root("root", false)]
+    #[serial]
+    fn tree_priority(#[case] priority: &str, #[case] reference_kept: bool) {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Content present in both trees and twice in the root tree
+        let root_path = test_case.root_dir_path.join("dir_5/file_2.txt");
+        let root_copy_path = test_case.root_dir_path.join("dir_5/file_2_copy.txt");
+        fs::copy(&root_path, &root_copy_path).unwrap();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("--priority")
+            .arg(priority)
+            .arg("--allow-root");
+        cmd.assert().success();
+
+        // Check exactly one copy survives in the priority tree
+        assert_eq!(test_case.file_path_1.exists(), reference_kept);
+        assert_eq!(root_path.exists(), !reference_kept);
+        assert!(!root_copy_path.exists());
+        assert!(test_case.file_path_2.exists());
+        assert!(test_case.root_dir_path.join("dir_5/file_3.txt").exists());
+
+        test_case.teardown();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    #[serial]
+    fn duplicates_hardlinked() {
+        use std::os::unix::fs::MetadataExt;
+
+        let test_case = CliTestCase::new();
+        test_case.startup();
+        let root_path = test_case.root_dir_path.join("dir_5/file_2.txt");
+        assert_eq!(fs::metadata(&root_path).unwrap().nlink(), 1);
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("--hardlink")
+            .arg("--allow-root");
+        cmd.assert()
+            .success()
+            .stderr(predicate::str::contains("Hardlinked 1 files"));
+
+        // Check the reference path is a hard link to the root file
+        let root_metadata = fs::metadata(&root_path).unwrap();
+        let reference_metadata = fs::metadata(&test_case.file_path_1).unwrap();
+        assert_eq!(root_metadata.nlink(), 2);
+        assert_eq!(reference_metadata.ino(), root_metadata.ino());
+        assert!(test_case.file_path_2.exists());
+
+        test_case.teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn duplicates_symlinked() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+        let root_path = test_case.root_dir_path.join("dir_5/file_2.txt");
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("--symlink")
+            .arg("--allow-root");
+        cmd.assert()
+            .success()
+            .stderr(predicate::str::contains("Symlinked 1 files"));
+
+        // Check the reference path is a symbolic link to the canonical root file
+        let metadata = fs::symlink_metadata(&test_case.file_path_1).unwrap();
+        assert!(metadata.file_type().is_symlink());
+        assert_eq!(
+            fs::read_link(&test_case.file_path_1).unwrap(),
+            root_path.canonicalize().unwrap()
+        );
+        assert!(test_case.file_path_2.exists());
+
+        test_case.teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn symlink_conflicts_with_hardlink() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("--symlink")
+            .arg("--hardlink");
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("cannot be used with"));
+
+        // Check results
+        assert!(test_case.file_path_1.exists());
+
+        test_case.teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn no_progress() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Execute program, the progress bar is hidden anyway as stdout is piped
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("--no-progress")
+            .arg("--allow-root");
+        cmd.assert()
+            .success()
+            .stderr(predicate::str::contains("Hashing").not());
+
+        // Check results
+        assert!(!test_case.file_path_1.exists());
+        assert!(test_case.file_path_2.exists());
+
+        test_case.teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn multiple_root_dirs() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Second root directory holding the only copy of a reference file
+        let second_root_dir_path = PathBuf::from("./test/test_root_2/");
+        fs::create_dir(&second_root_dir_path).unwrap_or(());
+        fs::write(second_root_dir_path.join("file.txt"), "test 50 200").unwrap();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg(&second_root_dir_path)
+            .arg("--allow-root");
+        cmd.assert().success();
+
+        // Check duplicates of both root directories are removed
+        assert!(!test_case.file_path_1.exists());
+        assert!(!test_case.file_path_2.exists());
+        assert!(second_root_dir_path.join("file.txt").exists());
+
+        fs::remove_dir_all(&second_root_dir_path).unwrap();
+        test_case.teardown();
+    }
+
+    #[rstest]
+    #[serial]
+    #[case::excluded("dir_[0-4]/", true)]
+    #[serial]
+    #[case::included("dir_5/", false)]
+    #[serial]
+    fn root_regex_filter(#[case] root_regex: &str, #[case] kept: bool) {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("--root-regex")
+            .arg(root_regex)
+            .arg("--regex")
+            .arg("file_test_")
+            .arg("--allow-root");
+        cmd.assert().success();
+
+        // Check the reference duplicate of dir_5/file_2.txt survives if the root file is excluded
+        assert_eq!(test_case.file_path_1.exists(), kept);
+        assert!(test_case.file_path_2.exists());
+
+        test_case.teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn hash_cache() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+        let cache_path = PathBuf::from("./test/test_cache.json");
+        fs::remove_file(&cache_path).unwrap_or(());
+
+        // Execute program twice, the second run reuses the cached hashes
+        let run = |args: &[&str]| {
+            let mut cmd = match Command::cargo_bin("dupsrm") {
+                Err(err) => panic!("{}", err),
+                Ok(cmd) => cmd,
+            };
+            cmd.arg(&test_case.reference_dir_path)
+                .arg(&test_case.root_dir_path)
+                .arg("--cache")
+                .arg(&cache_path)
+                .args(["--dry-run", "--no-verify", "--format", "json"])
+                .args(args);
+            let output = cmd.output().unwrap();
+            assert!(output.status.success());
+            serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap()
+        };
+        let first = run(&[]);
+        assert!(cache_path.exists());
+        assert_eq!(first.as_array().unwrap().len(), 1);
+        assert_eq!(run(&[]), first);
+
+        // Check a modified root file is hashed again
+        let root_path = test_case.root_dir_path.join("dir_5/file_2.txt");
+        fs::write(&root_path, "modified").unwrap();
+        assert!(run(&[]).as_array().unwrap().is_empty());
+
+        // Check entries of another algorithm aren't reused
+        fs::write(&root_path, "test 5 2").unwrap();
+        assert_eq!(
+            run(&["--hash-algorithm", "MD5"]).as_array().unwrap().len(),
+            1
+        );
+
+        fs::remove_file(&cache_path).unwrap();
+        test_case.teardown();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    #[serial]
+    fn unreadable_file_skipped() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let test_case = CliTestCase::new();
+        test_case.startup();
+        let unreadable_path = test_case.reference_dir_path.join("file_unreadable.txt");
+        fs::write(&unreadable_path, "test 0 0").unwrap();
+        fs::set_permissions(&unreadable_path, fs::Permissions::from_mode(0o000)).unwrap();
+        // Permissions don't restrict root
+        let unreadable = fs::File::open(&unreadable_path).is_err();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("--allow-root");
+        let assert = cmd.assert().success();
+        if unreadable {
+            assert.stderr(predicate::str::contains("Skipping file"));
+            assert!(unreadable_path.exists());
+        }
+
+        // Check the other files are processed
+        assert!(!test_case.file_path_1.exists());
+        assert!(test_case.file_path_2.exists());
+
+        fs::remove_file(&unreadable_path).unwrap_or(());
+        test_case.teardown();
+    }
+
+    #[cfg(unix)]
+    #[rstest]
+    #[serial]
+    #[case::lenient(&[], true)]
+    #[serial]
+    #[case::strict(&["--strict"], false)]
+    #[serial]
+    fn unreadable_dir(#[case] args: &[&str], #[case] success: bool) {
+        use std::os::unix::fs::PermissionsExt;
+
+        let test_case = CliTestCase::new();
+        test_case.startup();
+        let unreadable_dir_path = test_case.root_dir_path.join("dir_unreadable");
+        fs::create_dir(&unreadable_dir_path).unwrap();
+        fs::set_permissions(&unreadable_dir_path, fs::Permissions::from_mode(0o000)).unwrap();
+        // Permissions don't restrict root
+        let unreadable = fs::read_dir(&unreadable_dir_path).is_err();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .args(args)
+            .arg("--allow-root");
+        let assert = cmd.assert();
+        if unreadable && success {
+            assert
+                .success()
+                .stderr(predicate::str::contains("dir_unreadable"));
+        } else if unreadable {
+            assert.failure();
+        } else {
+            assert.success();
+        }
+
+        // Check results
+        assert_eq!(test_case.file_path_1.exists(), unreadable && !success);
+        assert!(test_case.file_path_2.exists());
+
+        fs::set_permissions(&unreadable_dir_path, fs::Permissions::from_mode(0o755)).unwrap();
+        test_case.teardown();
+    }
+
+    #[rstest]
+    #[serial]
+    #[case::abort(&[], 1)]
+    #[serial]
+    #[case::keep_going(&["--keep-going"], 2)]
+    #[serial]
+    fn removal_failure(#[case] args: &[&str], #[case] code: i32) {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // A directory in place of the temporary link makes replacing the duplicate fail
+        let blocking_path = test_case
+            .reference_dir_path
+            .join("file_test_6.txt.dupsrm-link");
+        fs::create_dir(&blocking_path).unwrap();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("--symlink")
+            .args(args)
+            .arg("--allow-root");
+        cmd.assert()
+            .code(code)
+            .stderr(predicate::str::contains("Symlinking 1 files failed"));
+
+        // Check results
+        assert!(test_case.file_path_1.is_file());
+        assert!(test_case.file_path_2.exists());
+
+        test_case.teardown();
+    }
+
+    #[rstest]
+    #[serial]
+    #[case::reference_in_root(
+        "./test/test_root/dir_5",
+        "./test/test_root",
+        "Reference directory is nested"
+    )]
+    #[serial]
+    #[case::root_in_reference(
+        "./test/test_root",
+        "./test/test_root/dir_5",
+        "is nested inside reference"
+    )]
+    #[serial]
+    fn overlapping_dirs(#[case] reference: &str, #[case] root: &str, #[case] msg: &str) {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+        let file_path = test_case.root_dir_path.join("dir_5/file_2.txt");
+
+        // Execute program without and with --allow-overlap
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(reference).arg(root).arg("--allow-root");
+        cmd.assert().failure().stderr(predicate::str::contains(msg));
+        assert!(file_path.exists());
+
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(reference)
+            .arg(root)
+            .arg("--allow-overlap")
+            .arg("--allow-root");
+        cmd.assert()
+            .success()
+            .stderr(predicate::str::contains(msg))
+            .stderr(predicate::str::contains("No duplicates found"));
+
+        // Check no file is matched with itself
+        assert!(file_path.exists());
+
+        test_case.teardown();
+    }
+
+    #[rstest]
+    #[serial]
+    #[case::skipped(&[], true)]
+    #[serial]
+    #[case::kept(&["--keep-empty"], false)]
+    #[serial]
+    fn empty_files(#[case] args: &[&str], #[case] kept: bool) {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Empty files in both trees
+        let empty_paths = [
+            test_case.reference_dir_path.join("file_empty_0.txt"),
+            test_case.reference_dir_path.join("file_empty_1.txt"),
+        ];
+        let root_empty_path = test_case.root_dir_path.join("dir_0/file_empty.txt");
+        for path in empty_paths.iter().chain([&root_empty_path]) {
+            fs::File::create(path).unwrap();
+        }
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .args(args)
+            .arg("--allow-root");
+        cmd.assert().success();
+
+        // Check empty reference files are only removed with --keep-empty
+        for path in &empty_paths {
+            assert_eq!(path.exists(), kept);
+        }
+        assert!(root_empty_path.exists());
+        assert!(!test_case.file_path_1.exists());
+        assert!(test_case.file_path_2.exists());
+
+        test_case.teardown();
+    }
+
+    #[rstest]
+    #[serial]
+    #[case::any_name(&[], false)]
+    #[serial]
+    #[case::same_name(&["--same-name-only"], true)]
+    #[serial]
+    fn same_name_only(#[case] args: &[&str], #[case] kept: bool) {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Reference duplicate with the same name as its root file
+        let same_name_path = test_case.reference_dir_path.join("file_3.txt");
+        fs::write(&same_name_path, "test 7 3").unwrap();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .args(args)
+            .arg("--allow-root");
+        cmd.assert().success();
+
+        // Check the duplicate with a different name is kept under --same-name-only
+        assert_eq!(test_case.file_path_1.exists(), kept);
+        assert!(!same_name_path.exists());
+        assert!(test_case.file_path_2.exists());
+
+        test_case.teardown();
+    }
+
+    #[rstest]
+    #[serial]
+    #[case::newline(&[], "\n")]
+    #[serial]
+    #[case::null(&["--null"], "\0")]
+    #[serial]
+    fn reference_list_stdin(#[case] args: &[&str], #[case] separator: &str) {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Duplicates in the reference directory, only some of them are listed
+        let listed_path = test_case.reference_dir_path.join("file_test_7.txt");
+        let unlisted_path = test_case.reference_dir_path.join("file_test_1.txt");
+        fs::write(&listed_path, "test 7 7").unwrap();
+        fs::write(&unlisted_path, "test 1 1").unwrap();
+        let list = [&test_case.file_path_1, &listed_path]
+            .map(|path| path.to_str().unwrap().to_owned())
+            .join(separator);
+
+        // Execute program
+        let mut cmd = match assert_cmd::Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg("-")
+            .arg(&test_case.root_dir_path)
+            .args(args)
+            .arg("--allow-root")
+            .write_stdin(list);
+        cmd.assert().success().stderr(predicate::str::contains(
+            "Reference files: 2 read from stdin",
+        ));
+
+        // Check only the listed files are considered
+        assert!(!test_case.file_path_1.exists());
+        assert!(!listed_path.exists());
+        assert!(unlisted_path.exists());
+        assert!(test_case.file_path_2.exists());
+
+        test_case.teardown();
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn one_file_system_device() {
+        let device = device_id(Path::new("test"));
+        assert!(device.is_some());
+        assert!(is_on_device(Path::new("test/test.txt"), device));
+        // procfs is always mounted as a filesystem of its own
+        assert!(!is_on_device(Path::new("/proc/self/status"), device));
+        assert!(is_on_device(Path::new("/proc/self/status"), None));
+    }
+
+    #[rstest]
+    #[case::serial(false, false)]
+    #[case::parallel(true, false)]
+    #[case::gitignore(false, true)]
+    #[cfg(target_os = "linux")]
+    fn one_file_system_walk(#[case] parallel: bool, #[case] gitignore: bool) {
+        // procfs is mounted below the filesystem root, so it is only walked across filesystems
+        let walk = |same_file_system| {
+            let options = WalkOptions {
+                parallel,
+                gitignore,
+                max_depth: Some(2),
+                same_file_system,
+                ..Default::default()
+            };
+            walk_files(Path::new("/"), &[], &options)
+        };
+        let cpuinfo = PathBuf::from("/proc/cpuinfo");
+        assert!(walk(false).contains(&cpuinfo));
+        assert!(!walk(true).iter().any(|path| path.starts_with("/proc")));
+    }
+
+    #[test]
+    #[serial]
+    fn keep_one_reference() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+        let copy_paths = ["copy_b.txt", "copy_a.txt", "copy_c.txt"]
+            .map(|name| test_case.reference_dir_path.join(name));
+        for path in &copy_paths {
+            fs::write(path, "test 5 3").unwrap();
+        }
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("--keep-one-reference")
+            .arg("--allow-root");
+        cmd.assert().success();
+
+        // Check results, the first of the identical reference files survives
+        let kept: Vec<bool> = copy_paths.iter().map(|path| path.exists()).collect();
+        assert_eq!(kept, vec![false, true, false]);
+        assert!(test_case.file_path_1.exists());
+        assert!(test_case.file_path_2.exists());
+
+        test_case.teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn path_lists_from_files() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Duplicates in the reference directory, only some of them and their copies are listed
+        let unlisted_path = test_case.reference_dir_path.join("file_test_1.txt");
+        let unlisted_copy_path = test_case.reference_dir_path.join("file_test_4.txt");
+        fs::write(&unlisted_path, "test 1 1").unwrap();
+        fs::write(&unlisted_copy_path, "test 4 4").unwrap();
+        let write_list = |name: &str, paths: &[&Path]| {
+            let list_path = test_case.reference_dir_path.join(name);
+            let mut list = Vec::new();
+            write_path_list(&mut list, paths.iter().copied(), true).unwrap();
+            fs::write(&list_path, list).unwrap();
+            list_path
+        };
+        let reference_list = write_list(
+            "reference.lst",
+            &[
+                test_case.file_path_1.as_path(),
+                test_case.file_path_2.as_path(),
+                unlisted_copy_path.as_path(),
+            ],
+        );
+        let root_list = write_list(
+            "root.lst",
+            &[
+                test_case.root_dir_path.join("dir_5/file_2.txt").as_path(),
+                test_case.root_dir_path.join("dir_1/file_1.txt").as_path(),
+            ],
+        );
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg("--reference-from")
+            .arg(&reference_list)
+            .arg("--root-from")
+            .arg(&root_list)
+            .arg("--allow-root");
+        cmd.assert()
+            .success()
+            .stderr(predicate::str::contains("Reference files: 3 read from"))
+            .stderr(predicate::str::contains("Root files: 2 read from"));
+
+        // Check results, no directory is walked
+        assert!(!test_case.file_path_1.exists());
+        assert!(test_case.file_path_2.exists());
+        assert!(unlisted_path.exists());
+        assert!(unlisted_copy_path.exists());
+        assert!(reference_list.exists());
+
+        test_case.teardown();
+    }
+
+    #[rstest]
+    #[serial]
+    #[case::exclude(&["--exclude", "*.tmp"], [true, true, false])]
+    #[serial]
+    #[case::include(&["--include", "*.jpg"], [false, true, false])]
+    #[serial]
+    #[case::include_exclude(
+        &["--include", "*.jpg", "--include", "*.tmp", "--exclude", "*.jpg"],
+        [false, false, true]
+    )]
+    #[serial]
+    fn glob_filter(#[case] args: &[&str], #[case] removed: [bool; 3]) {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Duplicates with different extensions
+        let tmp_path = test_case.reference_dir_path.join("file_test_7.tmp");
+        let jpg_path = test_case.reference_dir_path.join("file_test_7.jpg");
+        fs::write(&tmp_path, "test 7 7").unwrap();
+        fs::write(&jpg_path, "test 7 8").unwrap();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .args(args)
+            .arg("--allow-root");
+        cmd.assert().success();
+
+        // Check the selected duplicates are removed
+        assert_eq!(!test_case.file_path_1.exists(), removed[0]);
+        assert_eq!(!jpg_path.exists(), removed[1]);
+        assert_eq!(!tmp_path.exists(), removed[2]);
+        assert!(test_case.file_path_2.exists());
+
+        test_case.teardown();
+    }
+
+    #[cfg(unix)]
+    #[rstest]
+    #[serial]
+    #[case::not_followed(&[], true)]
+    #[serial]
+    #[case::followed(&["--follow-symlinks"], false)]
+    #[serial]
+    #[case::followed_strict(&["--follow-symlinks", "--strict"], false)]
+    #[serial]
+    #[case::followed_parallel(&["--follow-symlinks", "--parallel-walk"], false)]
+    #[serial]
+    #[case::followed_root(&["--follow-root-symlinks"], false)]
+    #[serial]
+    #[case::followed_reference(&["--follow-reference-symlinks"], true)]
+    #[serial]
+    fn follow_symlinks(#[case] args: &[&str], #[case] kept: bool) {
+        use std::os::unix::fs::symlink;
+
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Root file only reachable through a symbolic link and a link cycle in both trees
+        let linked_dir_path = PathBuf::from("./test/test_linked/");
+        fs::create_dir(&linked_dir_path).unwrap_or(());
+        fs::write(linked_dir_path.join("file.txt"), "test 50 200").unwrap();
+        symlink(
+            linked_dir_path.canonicalize().unwrap(),
+            test_case.root_dir_path.join("dir_linked"),
+        )
+        .unwrap();
+        symlink("..", test_case.root_dir_path.join("dir_0/cycle")).unwrap();
+        symlink(".", test_case.reference_dir_path.join("cycle")).unwrap();
+
+        // Execute program
+        let mut cmd = match assert_cmd::Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .args(args)
+            .timeout(Duration::from_secs(60))
+            .arg("--allow-root");
+        cmd.assert().success();
+
+        // Check the duplicate of the linked file is only found when following links
+        assert!(!test_case.file_path_1.exists());
+        assert_eq!(test_case.file_path_2.exists(), kept);
+        assert!(linked_dir_path.join("file.txt").exists());
+
+        fs::remove_dir_all(&linked_dir_path).unwrap();
+        test_case.teardown();
+    }
+
+    #[rstest]
+    #[case::serial(false)]
+    #[case::parallel(true)]
+    fn walk_errors(#[case] parallel: bool) {
+        let missing = Path::new("test/test_missing");
+        let options = WalkOptions {
+            parallel,
+            ..Default::default()
+        };
+        assert!(walk_files(missing, &[], &options).is_empty());
+        assert!(walk_files_strict(missing, &[], &options).is_err());
+        let file = Path::new("test/test.txt");
+        assert_eq!(walk_files_strict(file, &[], &options).unwrap().len(), 1);
+    }
+
+    #[test]
+    #[serial]
+    fn parallel_walk() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Both walks find the same files and prune the excluded directory
+        let excluded = [test_case.root_dir_path.join("dir_0")];
+        let walk = |parallel| {
+            let options = WalkOptions {
+                parallel,
+                ..Default::default()
+            };
+            let mut files = walk_files(&test_case.root_dir_path, &excluded, &options);
+            files.sort();
+            files
+        };
+        let files = walk(true);
+        assert_eq!(files, walk(false));
+        assert!(!files.is_empty());
+        assert!(files
+            .iter()
+            .all(|path| !is_subdirectory(path, &excluded[0])));
+
+        test_case.teardown();
+    }
+
+    #[rstest]
+    #[serial]
+    #[case::walkdir(&[])]
+    #[serial]
+    #[case::parallel(&["--parallel-walk"])]
+    #[serial]
+    #[case::gitignore(&["--respect-gitignore"])]
+    #[serial]
+    fn exclude_dir(#[case] walk_args: &[&str]) {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+        let excluded_dir = test_case.reference_dir_path.join("nested/node_modules");
+        fs::create_dir_all(&excluded_dir).unwrap();
+        let excluded_path = excluded_dir.join("file_test_3.txt");
+        fs::write(&excluded_path, "test 5 3").unwrap();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("--exclude-dir")
+            .arg("node_modules")
+            .args(walk_args)
+            .arg("--allow-root");
+        cmd.assert().success();
+
+        // Check results, the pruned directory is not descended into
+        assert!(!test_case.file_path_1.exists());
+        assert!(excluded_path.exists());
+
+        test_case.teardown();
+    }
+
+    #[rstest]
+    #[serial]
+    #[case::walkdir(&[])]
+    #[serial]
+    #[case::parallel(&["--parallel-walk"])]
+    #[serial]
+    #[case::gitignore(&["--respect-gitignore"])]
+    #[serial]
+    fn ignore_hidden(#[case] walk_args: &[&str]) {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+        let hidden_dir = test_case.reference_dir_path.join(".hidden");
+        fs::create_dir(&hidden_dir).unwrap();
+        let nested_path = hidden_dir.join("file_test_3.txt");
+        fs::write(&nested_path, "test 5 3").unwrap();
+        let hidden_path = test_case.reference_dir_path.join(".file_test_4.txt");
+        fs::write(&hidden_path, "test 5 4").unwrap();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("--ignore-hidden")
+            .args(walk_args)
+            .arg("--allow-root");
+        cmd.assert().success();
+
+        // Check results, hidden files and directories below the reference directory are kept
+        assert!(!test_case.file_path_1.exists());
+        assert!(nested_path.exists());
+        assert!(hidden_path.exists());
+
+        // A hidden reference directory itself is still walked
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&hidden_dir)
+            .arg(&test_case.root_dir_path)
+            .arg("--ignore-hidden")
+            .args(walk_args)
+            .arg("--allow-root");
+        cmd.assert().success();
+        assert!(!nested_path.exists());
+
+        test_case.teardown();
+    }
+
+    #[rstest]
+    #[serial]
+    #[case::walkdir(&[])]
+    #[serial]
+    #[case::parallel(&["--parallel-walk"])]
+    #[serial]
+    #[case::gitignore(&["--respect-gitignore"])]
+    #[serial]
+    fn max_depth(#[case] walk_args: &[&str]) {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+        let nested_dir = test_case.reference_dir_path.join("nested");
+        fs::create_dir(&nested_dir).unwrap();
+        let nested_path = nested_dir.join("file_test_3.txt");
+        fs::write(&nested_path, "test 5 3").unwrap();
+        let run = |depth_args: &[&str]| {
+            let mut cmd = match Command::cargo_bin("dupsrm") {
+                Err(err) => panic!("{}", err),
+                Ok(cmd) => cmd,
+            };
+            cmd.arg(&test_case.reference_dir_path)
+                .arg(&test_case.root_dir_path)
+                .args(depth_args)
+                .args(walk_args)
+                .arg("--allow-root");
+            cmd.assert().success();
+        };
+
+        // Root files are two levels deep, so none is known at depth 1
+        run(&["--root-max-depth", "1"]);
+        assert!(test_case.file_path_1.exists());
+        assert!(nested_path.exists());
+
+        // Only the direct children of the reference directory are walked at depth 1
+        run(&["--reference-max-depth", "1", "--root-max-depth", "2"]);
+        assert!(!test_case.file_path_1.exists());
+        assert!(nested_path.exists());
+        run(&["--max-depth", "1"]);
+        assert!(nested_path.exists());
+
+        // The nested file is reached one level further down
+        run(&["--reference-max-depth", "2"]);
+        assert!(!nested_path.exists());
+
+        test_case.teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn respect_gitignore() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+        let ignored_path = test_case.reference_dir_path.join("file_test_7.log");
+        fs::write(&ignored_path, "test 5 2").unwrap();
+        fs::write(test_case.reference_dir_path.join(".gitignore"), "*.log\n").unwrap();
+
+        // The ignored file is not walked, so it is never hashed
+        let options = WalkOptions {
+            gitignore: true,
+            ..Default::default()
+        };
+        let files = walk_files(&test_case.reference_dir_path, &[], &options);
+        assert!(!files.contains(&ignored_path));
+        assert!(files.contains(&test_case.file_path_1));
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("--respect-gitignore")
+            .arg("--allow-root");
+        cmd.assert().success();
+
+        // Check results
+        assert!(!test_case.file_path_1.exists());
+        assert!(ignored_path.exists());
+
+        test_case.teardown();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    #[serial]
+    fn regular_files_and_symlinks() {
+        let dir_path = PathBuf::from("./test/test_symlinks/");
+        fs::create_dir_all(&dir_path).unwrap();
+        let file_path = dir_path.join("file.txt");
+        let link_path = dir_path.join("link.txt");
+        let dangling_path = dir_path.join("dangling.txt");
+        fs::write(&file_path, "test").unwrap();
+        std::os::unix::fs::symlink("file.txt", &link_path).unwrap();
+        std::os::unix::fs::symlink("missing.txt", &dangling_path).unwrap();
+        assert!(is_dangling_symlink(&dangling_path));
+        assert!(!is_dangling_symlink(&link_path));
+        assert!(!is_dangling_symlink(&file_path));
+
+        // Without following links only the regular file is a file
+        for entry in walkdir::WalkDir::new(&dir_path).min_depth(1) {
+            let entry = entry.unwrap();
+            assert_eq!(is_file(&entry), entry.path() == file_path);
+            assert_eq!(is_regular_file(&entry), entry.path() == file_path);
+        }
+        // Following links the symbolic link is a file, but not a regular one
+        for entry in walkdir::WalkDir::new(&dir_path)
+            .follow_links(true)
+            .min_depth(1)
+            .into_iter()
+            .flatten()
+        {
+            assert!(is_file(&entry));
+            assert_eq!(is_regular_file(&entry), entry.path() == file_path);
+        }
+
+        // The dangling link is skipped instead of failing the walk or the hashing
+        let options = WalkOptions {
+            follow_links: true,
+            ..Default::default()
+        };
+        let mut files = walk_files_strict(&dir_path, &[], &options).unwrap();
+        files.sort();
+        assert_eq!(files, vec![file_path.clone(), link_path.clone()]);
+        let pairs = hash_files(files, sha256sum, &HashAlgorithm::SHA2_256, false);
+        assert_eq!(pairs.len(), 2);
+
+        fs::remove_dir_all(&dir_path).unwrap();
+    }
+
+    #[test]
+    #[serial]
+    fn hash_errors_skipped() {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Hash function failing for a single file
+        let files = walk_files(&test_case.reference_dir_path, &[], &WalkOptions::default());
+        let failing_path = test_case.file_path_1.canonicalize().unwrap();
+        let pairs = hash_files(
+            files,
+            |path: &Path| match path.canonicalize()? == failing_path {
+                true => Err(std::io::Error::other("failing")),
+                false => sha256sum(path),
+            },
+            &HashAlgorithm::SHA2_256,
+            false,
+        );
+
+        // Check the other file is hashed
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].1, test_case.file_path_2.canonicalize().unwrap());
+
+        test_case.teardown();
+    }
+
+    #[test]
+    fn match_strategies() {
+        // Files of equal size differing only after their first 8 bytes
+        let path_a = Path::new("test/test_strategy_a.txt");
+        let path_b = Path::new("test/test_strategy_b.txt");
+        fs::write(path_a, "prefix a").unwrap();
+        fs::write(path_b, "prefix b").unwrap();
+        let same = |path| FileInfo { path, hash: b"ab" };
+        let other = |path| FileInfo { path, hash: b"cd" };
+
+        // Exact hash strategy trusts the hash values only
+        assert!(ExactHash
+            .is_duplicate(&same(path_a), &same(path_b))
+            .unwrap());
+        assert!(!ExactHash
+            .is_duplicate(&same(path_a), &other(path_a))
+            .unwrap());
+
+        // Byte verify strategy compares the whole content
+        assert!(ByteVerify
+            .is_duplicate(&same(path_a), &same(path_a))
+            .unwrap());
+        assert!(!ByteVerify
+            .is_duplicate(&same(path_a), &same(path_b))
+            .unwrap());
+        assert!(!ByteVerify
+            .is_duplicate(&same(path_a), &other(path_a))
+            .unwrap());
+
+        // Prefix strategy compares the first bytes only
+        let prefix = Prefix { length: 7 };
+        assert!(prefix.is_duplicate(&same(path_a), &same(path_b)).unwrap());
+        assert!(!prefix.is_duplicate(&same(path_a), &other(path_a)).unwrap());
+        let prefix = Prefix { length: 8 };
+        assert!(!prefix.is_duplicate(&same(path_a), &same(path_b)).unwrap());
+
+        // Missing files are reported as errors
+        let missing = Path::new("test/test_strategy_missing.txt");
+        assert!(ByteVerify
+            .is_duplicate(&same(path_a), &same(missing))
+            .is_err());
+
+        fs::remove_file(path_a).unwrap();
+        fs::remove_file(path_b).unwrap();
+    }
+
+    #[rstest]
+    #[serial]
+    #[case::zero("0")]
+    #[serial]
+    #[case::short("d0b5")]
+    #[serial]
+    #[case::full("0123456789abcdef0123456789abcdef")]
+    #[serial]
+    fn index_key(#[case] key: &str) {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("--index-key")
+            .arg(key)
+            .arg("--allow-root");
+        cmd.assert().success();
+
+        // Check results are independent of the key
+        assert!(!test_case.file_path_1.exists());
+        assert!(test_case.file_path_2.exists());
+
+        test_case.teardown();
+    }
+
+    #[rstest]
+    #[serial]
+    #[case::sha2_256("SHA2-256")]
+    #[serial]
+    #[case::sha2_384("SHA2-384")]
+    #[serial]
+    #[case::sha2_512("SHA2-512")]
+    #[serial]
+    #[case::sha3_256("SHA3-256")]
+    #[serial]
+    #[case::sha1("SHA1")]
+    #[serial]
+    #[case::md5("MD5")]
+    #[serial]
+    #[case::whirlpool("WHIRLPOOL")]
+    #[serial]
+    #[case::ripemd160("RIPEMD-160")]
+    #[serial]
+    #[case::blake256("BLAKE-256")]
+    #[serial]
+    #[case::blake2b512("BLAKE2b-512")]
+    #[serial]
+    #[case::blake2s256("BLAKE2s-256")]
+    #[serial]
+    #[case::xxh3("XXH3")]
+    #[serial]
+    #[case::crc32("CRC32")]
+    #[serial]
+    fn hash_algorithms(#[case] alorithm: &str) {
+        let test_case = CliTestCase::new();
+        test_case.startup();
+
+        // Check prerequisites
+        assert!(test_case.file_path_1.exists());
+        assert!(test_case.file_path_2.exists());
+        assert!(test_case.root_dir_path.exists());
+        assert!(test_case.reference_dir_path.exists());
+
+        // Execute program
+        let mut cmd = match Command::cargo_bin("dupsrm") {
+            Err(err) => panic!("{}", err),
+            Ok(cmd) => cmd,
+        };
+        cmd.arg(&test_case.reference_dir_path)
+            .arg(&test_case.root_dir_path)
+            .arg("-a")
+            .arg(alorithm)
+            .arg("--allow-root");
+        cmd.assert().success();
+
+        // Check results
+        assert!(!test_case.file_path_1.exists());
+        assert!(test_case.file_path_2.exists());
+
+        test_case.teardown();
+    }
+
+    #[test]
+    fn hash_algorithm_names() {
+        // Check every variant round-trips through its name and has its empty hash listed
+        for algorithm in HashAlgorithm::VARIANTS {
+            let name = algorithm.to_string();
+            let parsed: HashAlgorithm = name.parse().unwrap();
+            assert_eq!(parsed.to_string(), name);
+            assert_eq!(empty_hash(&algorithm), hash_bytes(&[], &algorithm));
+        }
+        assert_eq!(HashAlgorithm::RIPEMD160.to_string(), "RIPEMD-160");
+
+        // Check unknown names are rejected with the valid names
+        let err = "SHA-256".parse::<HashAlgorithm>().err().unwrap();
+        assert!(err.contains("SHA-256"));
+        assert!(err.contains("SHA2-256"));
+    }
+
+    #[rstest]
+    #[case::sha2_256(HashAlgorithm::SHA2_256, sha256sum)]
+    #[case::sha2_384(HashAlgorithm::SHA2_384, sha384sum)]
+    #[case::sha2_512(HashAlgorithm::SHA2_512, sha512sum)]
+    #[case::sha3_256(HashAlgorithm::SHA3_256, sha3_256sum)]
+    #[case::sha1(HashAlgorithm::SHA1, sha1sum)]
+    #[case::md5(HashAlgorithm::MD5, md5sum)]
+    #[case::whirlpool(HashAlgorithm::WHIRLPOOL, whirlpool_sum)]
+    #[case::ripemd160(HashAlgorithm::RIPEMD160, ripemd160_sum)]
+    #[case::blake256(HashAlgorithm::BLAKE256, blake256_sum)]
+    #[case::blake2b512(HashAlgorithm::BLAKE2B512, blake2b512_sum)]
+    #[case::blake2s256(HashAlgorithm::BLAKE2S256, blake2s256_sum)]
+    #[case::xxh3(HashAlgorithm::XXH3, xxh3_sum)]
+    #[case::crc32(HashAlgorithm::CRC32, crc32_sum)]
+    fn hash_file_dispatch(
+        #[case] algorithm: HashAlgorithm,
+        #[case] hash_sum: fn(&Path) -> std::io::Result<Vec<u8>>,
+    ) {
+        let path: &Path = Path::new("test/test.txt");
+        assert_eq!(
+            hash_file(path, &algorithm).unwrap(),
+            hash_sum(path).unwrap()
+        );
+        // Contains text "test"
+        assert_eq!(hash_bytes(b"test", &algorithm), hash_sum(path).unwrap());
+        // Mapped and read files hash identically
+        for path in ["test/test.txt", "test/test_large", "test/test_empty.txt"] {
+            let path = Path::new(path);
+            for threshold in [0, u64::MAX] {
+                assert_eq!(
+                    hash_file_mapped(path, &algorithm, threshold).unwrap(),
+                    hash_sum(path).unwrap()
+                );
+            }
+        }
+    }
+
+    #[rstest]
+    #[case::sha2_256(
+        HashAlgorithm::SHA2_256,
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+    )]
+    #[case::sha1(HashAlgorithm::SHA1, "da39a3ee5e6b4b0d3255bfef95601890afd80709")]
+    #[case::md5(HashAlgorithm::MD5, "d41d8cd98f00b204e9800998ecf8427e")]
+    #[case::xxh3(HashAlgorithm::XXH3, "2d06800538d394c2")]
+    #[case::blake2s256(
+        HashAlgorithm::BLAKE2S256,
+        "69217a3079908094e11121d042354a7c1f55b6482ca1a51e1b250dfd1ed0eef9"
+    )]
+    #[case::crc32(HashAlgorithm::CRC32, "00000000")]
+    fn hex_round_trip(#[case] algorithm: HashAlgorithm, #[case] hex: &str) {
+        let hash = hash_bytes(&[], &algorithm);
+        assert_eq!(empty_hash(&algorithm), hash);
+        assert_eq!(to_hex(&hash), hex);
+        assert_eq!(from_hex(hex).unwrap(), hash);
+        assert_eq!(from_hex(&hex.to_uppercase()).unwrap(), hash);
+        // Odd lengths and non-hexadecimal digits are rejected
+        assert!(from_hex(&hex[1..]).is_err());
+        assert!(from_hex(&hex.replace('0', "g")).is_err());
+        assert!(from_hex(&format!("+{}", &hex[1..])).is_err());
+    }
+
+    /// Reader failing with a transient error until its failures are used up
+    struct FlakyReader<'a> {
+        failures: &'a Cell<u32>,
+        data: &'a [u8],
+    }
+
+    impl Read for FlakyReader<'_> {
+        fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+            if self.failures.get() > 0 {
+                self.failures.set(self.failures.get() - 1);
+                return Err(std::io::Error::from(std::io::ErrorKind::TimedOut));
+            }
+            self.data.read(buffer)
+        }
+    }
+
+    #[rstest]
+    #[case::no_retry(1, 0, false)]
+    #[case::retried(1, 1, true)]
+    #[case::retries_used_up(2, 1, false)]
+    fn io_retries(#[case] failures: u32, #[case] retries: u32, #[case] hashed: bool) {
+        let failures = Cell::new(failures);
+        let data = b"test 1 2";
+        let result = retrying(Path::new("test/test.txt"), retries, |_| {
+            let reader = FlakyReader {
+                failures: &failures,
+                data,
+            };
+            hash_reader(reader, &HashAlgorithm::SHA2_256)
+        });
+        match hashed {
+            true => assert_eq!(result.unwrap(), hash_bytes(data, &HashAlgorithm::SHA2_256)),
+            false => assert!(result.is_err()),
+        }
+
+        // Missing files aren't retried
+        let mut calls = 0;
+        let result = retrying(Path::new("test/test_missing"), 2, |path| {
+            calls += 1;
+            hash_file(path, &HashAlgorithm::SHA2_256)
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+
     #[rstest]
     #[case::sha2_256(HashAlgorithm::SHA2_256)]
+    #[case::sha2_384(HashAlgorithm::SHA2_384)]
+    #[case::sha2_512(HashAlgorithm::SHA2_512)]
     #[case::sha3_256(HashAlgorithm::SHA3_256)]
     #[case::sha1(HashAlgorithm::SHA1)]
     #[case::md5(HashAlgorithm::MD5)]
     #[case::whirlpool(HashAlgorithm::WHIRLPOOL)]
     #[case::ripemd160(HashAlgorithm::RIPEMD160)]
     #[case::blake256(HashAlgorithm::BLAKE256)]
+    #[case::blake2b512(HashAlgorithm::BLAKE2B512)]
+    #[case::blake2s256(HashAlgorithm::BLAKE2S256)]
+    #[case::xxh3(HashAlgorithm::XXH3)]
+    #[case::crc32(HashAlgorithm::CRC32)]
     fn hash_algorithms_empty(#[case] algorithm: HashAlgorithm) {
         let path: &Path = Path::new("test/test_empty.txt");
         let result = match algorithm {
             HashAlgorithm::SHA2_256 => sha256sum(path),
+            HashAlgorithm::SHA2_384 => sha384sum(path),
+            HashAlgorithm::SHA2_512 => sha512sum(path),
             HashAlgorithm::SHA3_256 => sha3_256sum(path),
             HashAlgorithm::SHA1 => sha1sum(path),
             HashAlgorithm::MD5 => md5sum(path),
             HashAlgorithm::WHIRLPOOL => whirlpool_sum(path),
             HashAlgorithm::RIPEMD160 => ripemd160_sum(path),
             HashAlgorithm::BLAKE256 => blake256_sum(path),
+            HashAlgorithm::BLAKE2B512 => blake2b512_sum(path),
+            HashAlgorithm::BLAKE2S256 => blake2s256_sum(path),
+            HashAlgorithm::XXH3 => xxh3_sum(path),
+            HashAlgorithm::CRC32 => crc32_sum(path),
         };
         assert!(is_empty_hash(&result.unwrap(), &algorithm));
     }